@@ -4,7 +4,11 @@ fn main() -> Result<()> {
     prost_build::Config::new()
         .type_attribute(
             "client.Height",
-            "#[derive(Copy, rlp_derive::RlpEncodable, rlp_derive::RlpDecodable)]",
+            "#[derive(Copy, Eq, Hash, PartialOrd, Ord, rlp_derive::RlpEncodable, rlp_derive::RlpDecodable)]",
+        )
+        .type_attribute(
+            "client.Height",
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]",
         )
         .compile_protos(
             &[
@@ -15,5 +19,13 @@ fn main() -> Result<()> {
             ],
             &["src/"],
         )?;
+
+    println!("cargo:rerun-if-changed=src/mol/ibc.mol");
+    molecule_codegen::Compiler::new()
+        .input_schema_file("src/mol/ibc.mol")
+        .generate_code(molecule_codegen::Language::Rust)
+        .output_dir_set_default()
+        .run()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
     Ok(())
 }
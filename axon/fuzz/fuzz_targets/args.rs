@@ -0,0 +1,16 @@
+#![no_main]
+
+use ckb_ics_axon::{ChannelArgs, ConnectionArgs, PacketArgs};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(args) = ConnectionArgs::from_slice(data) {
+        assert_eq!(args.encode(), data);
+    }
+    if let Ok(args) = ChannelArgs::from_slice(data) {
+        assert_eq!(ChannelArgs::is_open(data.to_vec()), Ok(args.open));
+    }
+    if let Ok(args) = PacketArgs::from_slice(data) {
+        assert_eq!(args.to_args(), data);
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use ckb_ics_axon::axon_client::{AxonClient, AxonCommitmentBatchProof, AxonCommitmentProof};
+use ckb_ics_axon::handler::Client;
+use ckb_ics_axon::proto::client::Height;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = rlp::decode::<AxonCommitmentBatchProof>(data);
+    let Ok(proof) = rlp::decode::<AxonCommitmentProof>(data) else {
+        return;
+    };
+    // Go past the height check into block and MPT verification.
+    let height = Height {
+        revision_number: 0,
+        revision_height: proof.block.header.number,
+    };
+    let client = AxonClient {
+        ibc_handler_address: [1; 20],
+        ..Default::default()
+    };
+    let _ = client.verify_membership(height, data, b"path", b"value");
+});
@@ -0,0 +1,37 @@
+#![no_main]
+
+use ckb_ics_axon::message::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(envelope) = rlp::decode::<Envelope>(data) else {
+        return;
+    };
+    let _ = envelope.verify_commitments_root();
+    let _ = commitments_root(&envelope.commitments);
+    // Content is decoded according to the message type.
+    let content = &envelope.content;
+    let _ = match envelope.msg_type {
+        MsgType::MsgClientCreate => rlp::decode::<MsgClientCreate>(content).map(drop),
+        MsgType::MsgClientUpdate => rlp::decode::<MsgClientUpdate>(content).map(drop),
+        MsgType::MsgConnectionOpenInit => rlp::decode::<MsgConnectionOpenInit>(content).map(drop),
+        MsgType::MsgConnectionOpenTry => rlp::decode::<MsgConnectionOpenTry>(content).map(drop),
+        MsgType::MsgConnectionOpenAck => rlp::decode::<MsgConnectionOpenAck>(content).map(drop),
+        MsgType::MsgConnectionOpenConfirm => {
+            rlp::decode::<MsgConnectionOpenConfirm>(content).map(drop)
+        }
+        MsgType::MsgChannelOpenInit => rlp::decode::<MsgChannelOpenInit>(content).map(drop),
+        MsgType::MsgChannelOpenTry => rlp::decode::<MsgChannelOpenTry>(content).map(drop),
+        MsgType::MsgChannelOpenAck => rlp::decode::<MsgChannelOpenAck>(content).map(drop),
+        MsgType::MsgChannelOpenConfirm => rlp::decode::<MsgChannelOpenConfirm>(content).map(drop),
+        MsgType::MsgChannelCloseInit => rlp::decode::<MsgChannelCloseInit>(content).map(drop),
+        MsgType::MsgChannelCloseConfirm => rlp::decode::<MsgChannelCloseConfirm>(content).map(drop),
+        MsgType::MsgSendPacket => rlp::decode::<MsgSendPacket>(content).map(drop),
+        MsgType::MsgRecvPacket => rlp::decode::<MsgRecvPacket>(content).map(drop),
+        MsgType::MsgWriteAckPacket => rlp::decode::<MsgWriteAckPacket>(content).map(drop),
+        MsgType::MsgAckPacket => rlp::decode::<MsgAckPacket>(content).map(drop),
+        MsgType::MsgTimeoutPacket => rlp::decode::<MsgTimeoutPacket>(content).map(drop),
+        MsgType::MsgConsumeAckPacket => rlp::decode::<MsgConsumeAckPacket>(content).map(drop),
+        MsgType::MsgClientMisbehaviour => Ok(()),
+    };
+});
@@ -0,0 +1,250 @@
+#![no_main]
+
+//! Arbitrary witnesses and cell data for all handlers.
+//!
+//! Input is an RLP list of a handler selector followed by the handler's
+//! inputs: RLP encoded objects and messages, and raw args. The client
+//! accepts every proof, so handlers get past verification to the checks of
+//! old and new cells. Handlers may reject anything but must not panic.
+
+use ckb_ics_axon::handler::*;
+use ckb_ics_axon::message::CommitmentKV;
+use ckb_ics_axon::object::VerifyError;
+use ckb_ics_axon::proto::client::Height;
+use ckb_ics_axon::{ChannelArgs, ConnectionArgs, PacketArgs};
+use libfuzzer_sys::fuzz_target;
+use rlp::{Decodable, Rlp};
+
+struct AcceptAll;
+
+impl Client for AcceptAll {
+    fn verify_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        _path: &[u8],
+        _value: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+
+    fn verify_non_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        _path: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+}
+
+impl ValidateSelfClient for AcceptAll {
+    fn validate_self_client(&self, _client_state: &[u8]) -> Result<(), VerifyError> {
+        Ok(())
+    }
+
+    fn validate_self_consensus_state(
+        &self,
+        _height: Height,
+        _consensus_state: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+}
+
+struct Witness<'a> {
+    rlp: Rlp<'a>,
+    next: usize,
+}
+
+impl Witness<'_> {
+    fn decode<T: Decodable>(&mut self) -> Option<T> {
+        let v = self.rlp.val_at(self.next).ok();
+        self.next += 1;
+        v
+    }
+
+    fn connection_args(&mut self) -> Option<ConnectionArgs> {
+        ConnectionArgs::from_slice(&self.decode::<Vec<u8>>()?).ok()
+    }
+
+    fn channel_args(&mut self) -> Option<ChannelArgs> {
+        ChannelArgs::from_slice(&self.decode::<Vec<u8>>()?).ok()
+    }
+
+    fn packet_args(&mut self) -> Option<PacketArgs> {
+        PacketArgs::from_slice(&self.decode::<Vec<u8>>()?).ok()
+    }
+}
+
+fn run(data: &[u8]) -> Option<Result<(), VerifyError>> {
+    let mut w = Witness {
+        rlp: Rlp::new(data),
+        next: 0,
+    };
+    let mut commitments: Vec<CommitmentKV> = Vec::new();
+    let c = &mut commitments;
+    let r = match w.decode::<u8>()? {
+        0 => handle_msg_connection_open_init(
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            c,
+        ),
+        1 => handle_msg_connection_open_try(
+            AcceptAll,
+            AcceptAll,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            c,
+            w.decode()?,
+        ),
+        2 => handle_msg_connection_open_ack(
+            AcceptAll,
+            AcceptAll,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            c,
+            w.decode()?,
+        ),
+        3 => handle_msg_connection_open_confirm(
+            AcceptAll,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            c,
+            w.decode()?,
+        ),
+        4 => handle_msg_channel_open_init(
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+        ),
+        5 => handle_msg_channel_open_try(
+            AcceptAll,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.connection_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+            w.decode()?,
+        ),
+        6 => handle_msg_channel_open_ack(
+            AcceptAll,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+            w.decode()?,
+        ),
+        7 => handle_msg_channel_open_confirm(
+            AcceptAll,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+            w.decode()?,
+        ),
+        8 => handle_msg_channel_close_init(
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+        ),
+        9 => handle_msg_channel_close_confirm(
+            AcceptAll,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            c,
+            w.decode()?,
+        ),
+        10 => handle_msg_send_packet(
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            c,
+        ),
+        11 => {
+            let old_channel = w.decode()?;
+            let old_channel_args = w.channel_args()?;
+            let new_channel = w.decode()?;
+            let new_channel_args = w.channel_args()?;
+            // Present for unordered channels only.
+            let useless_ibc_packet = w.decode();
+            handle_msg_recv_packet(
+                AcceptAll,
+                old_channel,
+                old_channel_args,
+                new_channel,
+                new_channel_args,
+                useless_ibc_packet,
+                w.decode()?,
+                w.packet_args()?,
+                c,
+                w.decode()?,
+            )
+        }
+        12 => handle_msg_ack_packet(
+            AcceptAll,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            c,
+            w.decode()?,
+        ),
+        13 => handle_msg_write_ack_packet(
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            c,
+        ),
+        14 => handle_msg_consume_ack_packet(w.decode()?, c),
+        15 => handle_msg_timeout_packet(
+            AcceptAll,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.channel_args()?,
+            w.decode()?,
+            w.packet_args()?,
+            c,
+            w.decode()?,
+        ),
+        _ => return None,
+    };
+    Some(r)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = run(data);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use ckb_ics_axon::handler::IbcChannel;
+use ckb_ics_axon::proto;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(channel) = rlp::decode::<IbcChannel>(data) {
+        // Handlers commit to the proto encoding of the channel.
+        let _ = proto::channel::Channel::from(channel);
+    }
+});
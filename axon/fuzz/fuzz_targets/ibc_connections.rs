@@ -0,0 +1,8 @@
+#![no_main]
+
+use ckb_ics_axon::handler::IbcConnections;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = rlp::decode::<IbcConnections>(data);
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use ckb_ics_axon::axon_client::verify::{verify_mpt, verify_mpt_multiproof};
+use libfuzzer_sys::fuzz_target;
+use rlp::Rlp;
+
+// Input is an RLP list of root, path and proof nodes.
+fuzz_target!(|data: &[u8]| {
+    let rlp = Rlp::new(data);
+    let (Ok(root), Ok(path), Ok(proof)) = (
+        rlp.val_at::<Vec<u8>>(0),
+        rlp.val_at::<Vec<u8>>(1),
+        rlp.list_at::<Vec<u8>>(2),
+    ) else {
+        return;
+    };
+    let value = verify_mpt(&root, &path, &proof);
+    // Multiproof accepts nodes in any order, so it proves at least what the
+    // sequential proof proves.
+    if let Ok(value) = value {
+        assert_eq!(
+            verify_mpt_multiproof(&root, &[&path], &proof).unwrap(),
+            [value]
+        );
+    }
+});
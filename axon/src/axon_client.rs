@@ -8,12 +8,16 @@ use molecule::prelude::*;
 use rlp_derive::{RlpDecodable, RlpEncodable};
 use tiny_keccak::{Hasher, Keccak};
 
-use crate::handler::Client;
+use crate::handler::{Client, ClientStatus};
 use crate::object::VerifyError;
 use crate::proto::client::Height;
+use crate::ConnectionArgs;
 
+pub mod slot;
 pub mod verify;
 
+use slot::CommitmentSlotLayout;
+
 // Make rlp_derive happy.
 pub type ProofNode = Vec<u8>;
 
@@ -26,13 +30,45 @@ pub struct AxonCommitmentProof {
     pub storage_proof: Vec<ProofNode>,
 }
 
+/// Proof of several commitments at the same height.
+///
+/// The block and the account proof are shared, so they are only verified
+/// once. Storage proof is a multiproof, so shared upper trie nodes are only
+/// included once.
+#[derive(RlpDecodable, RlpEncodable)]
+pub struct AxonCommitmentBatchProof {
+    pub block: AxonBlock,
+    pub previous_state_root: H256,
+    pub block_proof: AxonBlockProof,
+    pub account_proof: Vec<ProofNode>,
+    /// Deduplicated storage proof nodes of all paths, in any order.
+    pub storage_proof: Vec<ProofNode>,
+}
+
 #[derive(Default)]
 pub struct AxonClient {
     pub ibc_handler_address: [u8; 20],
     pub validators: RefCell<Vec<ValidatorExtend>>,
+    /// Storage layout of commitments in the IBC handler contract.
+    pub slot_layout: CommitmentSlotLayout,
+    /// `Active` from the constructors. Metadata cells don't record
+    /// misbehaviour or expiry, so callers that track them, e.g. in a client
+    /// cell, set the status with `with_status`.
+    pub status: ClientStatus,
+    /// Latest trusted height of the chain, set by the constructors. Proofs
+    /// after it or on another revision are rejected.
+    pub latest_height: Option<Height>,
 }
 
 impl Client for AxonClient {
+    fn status(&self) -> ClientStatus {
+        self.status
+    }
+
+    fn latest_height(&self) -> Option<Height> {
+        self.latest_height
+    }
+
     fn verify_membership(
         &self,
         height: Height,
@@ -44,9 +80,67 @@ impl Client for AxonClient {
         path: &[u8],
         value: &[u8],
     ) -> Result<(), VerifyError> {
-        // Skip verification when ibc_handler_address is zero.
+        self.verify_slot_value(height, proof, path, keccak256(value))
+    }
+
+    fn verify_non_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError> {
+        self.verify_slot_value(height, proof, path, [0; 32])
+    }
+
+    fn verify_membership_batch(
+        &self,
+        height: Height,
+        proof: &[u8],
+        kvs: &[(&[u8], &[u8])],
+    ) -> Result<(), VerifyError> {
         if self.ibc_handler_address == [0; 20] {
-            return Ok(());
+            return skip_verification();
+        }
+
+        let AxonCommitmentBatchProof {
+            block,
+            previous_state_root,
+            block_proof,
+            account_proof,
+            storage_proof,
+        } = rlp::decode(proof).map_err(|_| VerifyError::SerdeError)?;
+
+        let block_state_root =
+            self.verify_block(height, block, previous_state_root, block_proof)?;
+
+        let storage_root = verify::verify_account(
+            block_state_root.as_bytes(),
+            &self.ibc_handler_address,
+            &account_proof,
+        )?;
+        verify::verify_storage_multiproof(
+            storage_root,
+            kvs.iter()
+                .map(|(path, value)| (self.slot_layout.slot(path), keccak256(value))),
+            &storage_proof,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl AxonClient {
+    /// Verify that the storage slot of `path` has `slot_value`, zero if it
+    /// doesn't exist.
+    fn verify_slot_value(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+        slot_value: [u8; 32],
+    ) -> Result<(), VerifyError> {
+        if self.ibc_handler_address == [0; 20] {
+            return skip_verification();
         }
 
         let AxonCommitmentProof {
@@ -57,8 +151,32 @@ impl Client for AxonClient {
             block_proof,
         } = rlp::decode(proof).map_err(|_| VerifyError::SerdeError)?;
 
+        let block_state_root =
+            self.verify_block(height, block, previous_state_root, block_proof)?;
+
+        verify::verify_account_and_storage(
+            block_state_root.as_bytes(),
+            &self.ibc_handler_address,
+            &account_proof,
+            self.slot_layout.slot(path),
+            slot_value,
+            &storage_proof,
+        )?;
+
+        Ok(())
+    }
+
+    /// Verify the block and its proof, returns state root of the block.
+    fn verify_block(
+        &self,
+        height: Height,
+        block: AxonBlock,
+        previous_state_root: H256,
+        block_proof: AxonBlockProof,
+    ) -> Result<H256, VerifyError> {
         let block_state_root = block.header.state_root;
 
+        self.validate_proof_height(height)?;
         if height.revision_height != block.header.number {
             return Err(VerifyError::Mpt);
         }
@@ -71,27 +189,18 @@ impl Client for AxonClient {
         )
         .map_err(|_| VerifyError::InvalidReceiptProof)?;
 
-        verify::verify_account_and_storage(
-            block_state_root.as_bytes(),
-            &self.ibc_handler_address,
-            &account_proof,
-            commitment_slot(path),
-            keccak256(value),
-            &storage_proof,
-        )?;
-
-        Ok(())
+        Ok(block_state_root)
     }
-}
 
-impl AxonClient {
+    /// Client with the validators in `metadata_cell_data`, trusting the
+    /// chain up to `latest_height`, e.g. the height in the client cell.
     pub fn new(
         ibc_handler_address: [u8; 20],
         metadata_cell_data: &[u8],
+        latest_height: Height,
     ) -> Result<Self, VerifyError> {
-        // Skip verification when ibc_handler_address is zero.
         if ibc_handler_address == [0; 20] {
-            return Ok(Self::default());
+            return skip_verification().map(|_| Self::default());
         }
 
         let metadata_cell_data = MetadataCellDataReader::from_slice(metadata_cell_data)
@@ -109,9 +218,8 @@ impl AxonClient {
                 .raw_data()
                 .try_into()
                 .map_err(|_| VerifyError::SerdeError)?;
-            let propose_weight =
-                u32::from_le_bytes(v.propose_weight().as_slice().try_into().unwrap());
-            let vote_weight = u32::from_le_bytes(v.vote_weight().as_slice().try_into().unwrap());
+            let propose_weight = read_u32(v.propose_weight().as_slice())?;
+            let vote_weight = read_u32(v.vote_weight().as_slice())?;
             let validator = ValidatorExtend {
                 bls_pub_key: bls_pub_key.into(),
                 pub_key: pub_key.into(),
@@ -124,11 +232,61 @@ impl AxonClient {
         Ok(AxonClient {
             ibc_handler_address,
             validators: validators.into(),
+            slot_layout: CommitmentSlotLayout::default(),
+            status: ClientStatus::Active,
+            latest_height: Some(latest_height),
         })
     }
+
+    /// Client of the chain in connection `args` from its metadata cell.
+    ///
+    /// The metadata cell is a cell dep, so anyone can provide one. Its
+    /// validators are only trusted if the type script hash of the cell,
+    /// `None` when it has no type script, is `args.metadata_type_id`.
+    pub fn from_metadata_cell(
+        args: &ConnectionArgs,
+        metadata_type_hash: Option<[u8; 32]>,
+        metadata_cell_data: &[u8],
+        latest_height: Height,
+    ) -> Result<Self, VerifyError> {
+        if metadata_type_hash != Some(args.metadata_type_id) {
+            return Err(VerifyError::WrongMetadataCell);
+        }
+        Self::new(args.ibc_handler_address, metadata_cell_data, latest_height)
+    }
+
+    /// Use another storage layout of commitments, for IBC handler contracts
+    /// other than ibc-solidity.
+    pub fn with_slot_layout(mut self, slot_layout: CommitmentSlotLayout) -> Self {
+        self.slot_layout = slot_layout;
+        self
+    }
+
+    /// Set the status of the client. Handlers reject all proofs of a client
+    /// that isn't `Active`.
+    pub fn with_status(mut self, status: ClientStatus) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+fn read_u32(slice: &[u8]) -> Result<u32, VerifyError> {
+    let bytes = slice.try_into().map_err(|_| VerifyError::SerdeError)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// A zero IBC handler address turns off proof verification. This is a
+/// development shortcut, so it is rejected unless the
+/// `insecure-skip-verification` feature is enabled.
+fn skip_verification() -> Result<(), VerifyError> {
+    if cfg!(feature = "insecure-skip-verification") {
+        Ok(())
+    } else {
+        Err(VerifyError::ZeroIBCHandlerAddress)
+    }
 }
 
-fn keccak256(slice: &[u8]) -> [u8; 32] {
+pub(crate) fn keccak256(slice: &[u8]) -> [u8; 32] {
     let mut hasher = Keccak::v256();
     hasher.update(slice);
     let mut output = [0u8; 32];
@@ -136,12 +294,25 @@ fn keccak256(slice: &[u8]) -> [u8; 32] {
     output
 }
 
-/// Storage slot for commitment path.
+/// Storage slot for commitment path in ibc-solidity.
 pub fn commitment_slot(path: &[u8]) -> [u8; 32] {
-    let mut h = Keccak::v256();
-    h.update(&keccak256(path));
-    h.update(&[0u8; 32]);
-    let mut o = [0u8; 32];
-    h.finalize(&mut o);
-    o
+    CommitmentSlotLayout::IBC_SOLIDITY.slot(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_ibc_handler_address() {
+        let client = AxonClient::new([0; 20], &[], Height::default());
+        let verified = AxonClient::default().verify_membership(Height::default(), &[], b"", b"");
+        if cfg!(feature = "insecure-skip-verification") {
+            assert!(client.is_ok());
+            assert!(verified.is_ok());
+        } else {
+            assert!(matches!(client, Err(VerifyError::ZeroIBCHandlerAddress)));
+            assert!(matches!(verified, Err(VerifyError::ZeroIBCHandlerAddress)));
+        }
+    }
 }
@@ -0,0 +1,156 @@
+//! Storage slots of commitments in IBC handler contracts.
+
+use alloc::vec::Vec;
+
+use super::keccak256;
+
+// Storage location of commitments in yui-ibc-solidity v0.3, per ERC-7201:
+// keccak256(abi.encode(uint256(keccak256("ibc.commitment")) - 1)) & ~bytes32(uint256(0xff))
+// 0x1ee222554989dda120e26ecacf756fe1235cd8d726706b57517715dde4f0c900
+const YUI_IBC_SOLIDITY_V0_3_LOCATION: [u8; 32] = [
+    30, 226, 34, 85, 73, 137, 221, 161, 32, 226, 110, 202, 207, 117, 111, 225, 35, 92, 216, 215,
+    38, 112, 107, 87, 81, 119, 21, 221, 228, 240, 201, 0,
+];
+
+/// How an IBC handler contract lays out commitments in its storage.
+///
+/// Commitment values are always `keccak(value)`, only the storage slot of a
+/// path differs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitmentSlotLayout {
+    /// `mapping(bytes32 => bytes32)` at storage slot `slot` (big endian
+    /// uint256), indexed by `keccak(path)`.
+    Mapping { slot: [u8; 32] },
+    /// Nested mappings at storage slot `slot`. The outer mappings are indexed
+    /// by `keys` in order, and the innermost mapping by `keccak(path)`.
+    NestedMapping { slot: [u8; 32], keys: Vec<[u8; 32]> },
+    /// Storage slot is `keccak(path)` itself.
+    RawKeyHash,
+}
+
+impl CommitmentSlotLayout {
+    /// ibc-solidity: `mapping(bytes32 => bytes32) commitments` at slot 0.
+    pub const IBC_SOLIDITY: Self = Self::Mapping { slot: [0; 32] };
+
+    /// yui-ibc-solidity v0.3: `mapping(bytes32 => bytes32) commitments` at
+    /// the ERC-7201 namespaced location "ibc.commitment".
+    pub const YUI_IBC_SOLIDITY_V0_3: Self = Self::Mapping {
+        slot: YUI_IBC_SOLIDITY_V0_3_LOCATION,
+    };
+
+    /// Mapping at storage slot `index`.
+    pub fn mapping(index: u64) -> Self {
+        Self::Mapping {
+            slot: slot_from_index(index),
+        }
+    }
+
+    /// Storage slot of commitment path.
+    pub fn slot(&self, path: &[u8]) -> [u8; 32] {
+        self.slot_of_hashed_path(keccak256(path))
+    }
+
+    /// Storage slot of commitment path, given `keccak(path)`.
+    pub fn slot_of_hashed_path(&self, path_hash: [u8; 32]) -> [u8; 32] {
+        match self {
+            Self::Mapping { slot } => mapping_slot(slot, &path_hash),
+            Self::NestedMapping { slot, keys } => {
+                let slot = keys.iter().fold(*slot, |slot, k| mapping_slot(&slot, k));
+                mapping_slot(&slot, &path_hash)
+            }
+            Self::RawKeyHash => path_hash,
+        }
+    }
+}
+
+impl Default for CommitmentSlotLayout {
+    fn default() -> Self {
+        Self::IBC_SOLIDITY
+    }
+}
+
+fn slot_from_index(index: u64) -> [u8; 32] {
+    let mut slot = [0u8; 32];
+    slot[24..].copy_from_slice(&index.to_be_bytes());
+    slot
+}
+
+/// Storage slot of `key` in a solidity mapping at `slot`.
+fn mapping_slot(slot: &[u8; 32], key: &[u8; 32]) -> [u8; 32] {
+    keccak256(&[&key[..], slot].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axon_client::commitment_slot;
+
+    fn slot_hex(layout: &CommitmentSlotLayout, path: &[u8]) -> String {
+        hex::encode(layout.slot(path))
+    }
+
+    #[test]
+    fn test_ibc_solidity_slot() {
+        let layout = CommitmentSlotLayout::IBC_SOLIDITY;
+        assert_eq!(layout, CommitmentSlotLayout::default());
+        assert_eq!(layout, CommitmentSlotLayout::mapping(0));
+        assert_eq!(layout.slot(b"abc"), commitment_slot(b"abc"));
+        assert_eq!(
+            slot_hex(&layout, b"abc"),
+            "0d4296a21c97b0dcb7b57560158cf9656e6090259d85f0fcf67e395bf96c83e7"
+        );
+    }
+
+    #[test]
+    fn test_yui_ibc_solidity_v0_3_slot() {
+        let mut index = keccak256(b"ibc.commitment");
+        // uint256(keccak256("ibc.commitment")) - 1, the last byte is non-zero.
+        index[31] -= 1;
+        let mut location = keccak256(&index);
+        location[31] = 0;
+        assert_eq!(location, YUI_IBC_SOLIDITY_V0_3_LOCATION);
+
+        assert_eq!(
+            slot_hex(&CommitmentSlotLayout::YUI_IBC_SOLIDITY_V0_3, b"abc"),
+            "2382c9c6dbda51f9873c62203d21df7b48bd3c043770acb3d856d49a65c83db9"
+        );
+    }
+
+    #[test]
+    fn test_mapping_slot() {
+        assert_eq!(
+            slot_hex(&CommitmentSlotLayout::mapping(5), b"abc"),
+            "6e53d0010c1529775a0dbbb6d4b28296428b9b24d165a5fbbae9e8f53a5f0095"
+        );
+    }
+
+    #[test]
+    fn test_nested_mapping_slot() {
+        let layout = CommitmentSlotLayout::NestedMapping {
+            slot: slot_from_index(5),
+            keys: vec![[1; 32]],
+        };
+        assert_eq!(
+            slot_hex(&layout, b"abc"),
+            "5961b7b40a2dd147367ec888d05fe0b0e2f0a88a84707f0a10777f82864dcdea"
+        );
+
+        // Without outer keys it's a plain mapping.
+        let layout = CommitmentSlotLayout::NestedMapping {
+            slot: slot_from_index(5),
+            keys: vec![],
+        };
+        assert_eq!(
+            layout.slot(b"abc"),
+            CommitmentSlotLayout::mapping(5).slot(b"abc")
+        );
+    }
+
+    #[test]
+    fn test_raw_key_hash_slot() {
+        assert_eq!(
+            slot_hex(&CommitmentSlotLayout::RawKeyHash, b"abc"),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+    }
+}
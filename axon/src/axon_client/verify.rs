@@ -1,3 +1,5 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use bytes::Bytes;
 use ethereum_types::U256;
 use rlp::Rlp;
@@ -26,7 +28,7 @@ pub fn verify_mpt<'b, T: AsRef<[u8]>>(
 
     let mut proof_iter = proof.iter();
 
-    let mut get_node_by_hash = |h: &[u8]| {
+    let get_node_by_hash = |h: &[u8]| {
         let next = proof_iter.next().ok_or(VerifyError::Mpt)?;
         if h == keccak256(next.as_ref()) {
             Ok(Rlp::new(next.as_ref()))
@@ -35,6 +37,66 @@ pub fn verify_mpt<'b, T: AsRef<[u8]>>(
         }
     };
 
+    get_by_path(root, path, get_node_by_hash)
+}
+
+/// Deduplicated proof nodes of several paths in the same MPT, keyed by hash.
+///
+/// Shared upper nodes only need to be included once, so this is smaller than
+/// separate proofs for each path.
+pub struct MultiProof<'b> {
+    nodes: BTreeMap<[u8; 32], &'b [u8]>,
+}
+
+impl<'b> MultiProof<'b> {
+    /// Build a multiproof from proof nodes in any order.
+    pub fn new<T: AsRef<[u8]>>(nodes: &'b [T]) -> Self {
+        Self {
+            nodes: nodes
+                .iter()
+                .map(|n| (keccak256(n.as_ref()), n.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Get value by path from MPT root.
+    ///
+    /// Returns an empty slice if the path can be proven not exist.
+    pub fn verify(&self, root: &[u8], path: &[u8]) -> Result<&'b [u8], VerifyError> {
+        if root == EMPTY_ROOT {
+            return Ok(&[]);
+        }
+
+        get_by_path(root, path, |h| {
+            let h: [u8; 32] = h.try_into().map_err(|_| VerifyError::Mpt)?;
+            let node = self.nodes.get(&h).copied().ok_or(VerifyError::Mpt)?;
+            Ok(Rlp::new(node))
+        })
+    }
+}
+
+/// Get values of several paths from MPT root and a deduplicated set of proof
+/// nodes.
+///
+/// Values are returned in the same order as `paths`. A value is an empty
+/// slice if the path can be proven not exist.
+pub fn verify_mpt_multiproof<'b, T: AsRef<[u8]>, P: AsRef<[u8]>>(
+    root: &[u8],
+    paths: &[P],
+    proof: &'b [T],
+) -> Result<Vec<&'b [u8]>, VerifyError> {
+    let proof = MultiProof::new(proof);
+    paths
+        .iter()
+        .map(|p| proof.verify(root, p.as_ref()))
+        .collect()
+}
+
+fn get_by_path<'b>(
+    root: &[u8],
+    path: &[u8],
+    mut get_node_by_hash: impl FnMut(&[u8]) -> Result<Rlp<'b>, VerifyError>,
+) -> Result<&'b [u8], VerifyError> {
     let path_nibbles = path.len() * 2;
     let mut node = get_node_by_hash(root)?;
     let mut path_offset = 0;
@@ -50,7 +112,7 @@ pub fn verify_mpt<'b, T: AsRef<[u8]>>(
             }
 
             // Branch by next nibble.
-            let nibble = get_nibble(path, path_offset) as usize;
+            let nibble = get_nibble(path, path_offset)? as usize;
             let next = node.at(nibble)?;
             path_offset += 1;
             if next.is_empty() {
@@ -108,8 +170,7 @@ fn skip_length(node: &[u8]) -> Result<(usize, bool), VerifyError> {
         return Err(VerifyError::Mpt);
     }
 
-    let nibble = get_nibble(node, 0);
-    match nibble {
+    match get_nibble(node, 0)? {
         0 => Ok((2, false)),
         1 => Ok((1, false)),
         2 => Ok((2, true)),
@@ -118,17 +179,17 @@ fn skip_length(node: &[u8]) -> Result<(usize, bool), VerifyError> {
     }
 }
 
-fn get_nibble(path: &[u8], offset: usize) -> u8 {
-    let byte = path[offset / 2];
+fn get_nibble(path: &[u8], offset: usize) -> Result<u8, VerifyError> {
+    let byte = path.get(offset / 2).ok_or(VerifyError::Mpt)?;
     if offset % 2 == 0 {
-        byte >> 4
+        Ok(byte >> 4)
     } else {
-        byte & 0xF
+        Ok(byte & 0xF)
     }
 }
 
 fn nibbles(buf: &[u8], offset: usize) -> impl Iterator<Item = u8> + '_ {
-    (offset..buf.len() * 2).map(|o| get_nibble(buf, o))
+    buf.iter().flat_map(|b| [b >> 4, b & 0xF]).skip(offset)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -140,18 +201,56 @@ pub fn verify_account_and_storage<T1: AsRef<[u8]>, T2: AsRef<[u8]>>(
     slot_value: [u8; 32],
     storage_proof: &[T2],
 ) -> Result<(), VerifyError> {
+    let storage_root = verify_account(state_root, address, account_proof)?;
+    verify_storage(storage_root, slot, slot_value, storage_proof)
+}
+
+/// Verify account proof and return storage root of the account.
+pub fn verify_account<'b, T: AsRef<[u8]>>(
+    state_root: &[u8],
+    address: &[u8],
+    account_proof: &'b [T],
+) -> Result<&'b [u8], VerifyError> {
     let address_hash = keccak256(address);
     let account_bytes = verify_mpt(state_root, &address_hash, account_proof)?;
     // Account is (nonce, balance, storage_root, code_hash), so storage root is at index 2.
-    let storage_root = Rlp::new(account_bytes).at(2)?.data()?;
+    Ok(Rlp::new(account_bytes).at(2)?.data()?)
+}
+
+/// Verify that storage `slot` has value `slot_value`.
+///
+/// A zero `slot_value` means the slot doesn't exist.
+pub fn verify_storage<T: AsRef<[u8]>>(
+    storage_root: &[u8],
+    slot: [u8; 32],
+    slot_value: [u8; 32],
+    storage_proof: &[T],
+) -> Result<(), VerifyError> {
+    let trie_value = verify_mpt(storage_root, &keccak256(&slot), storage_proof)?;
+    check_storage_value(trie_value, slot_value)
+}
+
+/// Verify that each storage slot has its value, with a deduplicated set of
+/// proof nodes for all slots.
+pub fn verify_storage_multiproof<T: AsRef<[u8]>>(
+    storage_root: &[u8],
+    slots: impl IntoIterator<Item = ([u8; 32], [u8; 32])>,
+    storage_proof: &[T],
+) -> Result<(), VerifyError> {
+    let proof = MultiProof::new(storage_proof);
+    for (slot, slot_value) in slots {
+        let trie_value = proof.verify(storage_root, &keccak256(&slot))?;
+        check_storage_value(trie_value, slot_value)?;
+    }
+    Ok(())
+}
 
-    let slot_hash = keccak256(&slot);
+fn check_storage_value(trie_value: &[u8], slot_value: [u8; 32]) -> Result<(), VerifyError> {
     let expected_trie_value = if slot_value != [0; 32] {
         rlp::encode(&U256::from(&slot_value)).freeze()
     } else {
         Bytes::new()
     };
-    let trie_value = verify_mpt(storage_root, &slot_hash, storage_proof)?;
 
     if trie_value != expected_trie_value {
         return Err(VerifyError::Mpt);
@@ -161,12 +260,44 @@ pub fn verify_account_and_storage<T1: AsRef<[u8]>, T2: AsRef<[u8]>>(
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use proptest::prelude::*;
 
     use super::*;
     use crate::axon_client::commitment_slot;
 
+    fn test_verify_mpt_multiproof(kvs: Vec<(Vec<u8>, Vec<u8>)>, pks: Vec<Vec<u8>>) {
+        use std::collections::BTreeSet;
+        use std::sync::Arc;
+
+        use hasher::HasherKeccak;
+
+        use cita_trie::MemoryDB;
+        use cita_trie::{PatriciaTrie, Trie};
+
+        let memdb = Arc::new(MemoryDB::new(false));
+        let hasher = Arc::new(HasherKeccak::new());
+
+        let mut trie = PatriciaTrie::new(Arc::clone(&memdb), Arc::clone(&hasher));
+
+        for (k, v) in kvs {
+            trie.insert(k, v.to_vec()).unwrap();
+        }
+        let root = trie.root().unwrap();
+
+        let mut nodes = BTreeSet::new();
+        for pk in &pks {
+            nodes.extend(trie.get_proof(pk).unwrap());
+        }
+        let nodes: Vec<Vec<u8>> = nodes.into_iter().collect();
+
+        let values = verify_mpt_multiproof(&root, &pks, &nodes).unwrap();
+        assert_eq!(values.len(), pks.len());
+        for (pk, v) in pks.iter().zip(values) {
+            assert_eq!(v, trie.get(pk).unwrap().unwrap_or_default());
+        }
+    }
+
     fn test_verify_mpt(kvs: Vec<(Vec<u8>, Vec<u8>)>, pks: Vec<Vec<u8>>) {
         use std::sync::Arc;
 
@@ -192,25 +323,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_nibbles() {
+        assert_eq!(get_nibble(&[0x12], 0).unwrap(), 1);
+        assert_eq!(get_nibble(&[0x12], 1).unwrap(), 2);
+        assert!(get_nibble(&[0x12], 2).is_err());
+        assert_eq!(nibbles(&[0x12, 0x34], 1).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(nibbles(&[0x12], 3).count(), 0);
+    }
+
     proptest!(
         #[test]
         fn prop_test_verify_mpt(kvs: Vec<(Vec<u8>, Vec<u8>)>, pks: Vec<Vec<u8>>) {
             test_verify_mpt(kvs, pks)
         }
+
+        #[test]
+        fn prop_test_verify_mpt_multiproof(kvs: Vec<(Vec<u8>, Vec<u8>)>, pks: Vec<Vec<u8>>) {
+            test_verify_mpt_multiproof(kvs, pks)
+        }
     );
 
+    // eth_getProof result from ganache, proving that commitment path "abc" has
+    // value "def".
+    pub(crate) const GANACHE_STATE_ROOT: &str =
+        "b05361ee4e2433d107e7bbd512d906b0b9cb9b7122636dff7fdb74f78c16f551";
+    pub(crate) const GANACHE_IBC_HANDLER_ADDRESS: &str = "1C6e2aAcAf61711A2dD74d18363766482d93CF84";
+    pub(crate) const GANACHE_ACCOUNT_PROOF: [&str; 4] = ["0xf90211a021ff4e4e9ef3e4206823799dc4181bef914f590200c1ba58d8b517ec2ec902e9a0fa22b58ff107979c4ecb0d18dcc4c9c4a21d82a5f49e18bc84a6dcc1f43c6cffa05f401c8ccf28c88c795c8dc3194c10de0364f121365be2f81c89ae9a4466ac4aa07d55b967ed900e13b3dd0794dd7284d6a84b6aeb0da2d0c22ae3c1d46206e51ba0438cfa73f409f90f93f88859ba2a249b158341547c715c9ea05863f25e0d872fa02245741cb87ce55bef07c0dded7d132b406a961a3876d3126a5c770902097551a04c5a9fe5ecc0f2400e2ea6eeae97bbbed17e40e2c95bc60044f4f8bd7d502049a07f0773a09800a67a39a15b889e2f777caffab9cbd7d44e7749f92ea78b4ab188a050e2407b752610686f21766779ef5184561d1280387ad40e190429fccc9dc1bca0ea55baaf73e67e8d7bf88847ef7ed8d11cfc1731174555a9fb9092e704e4b9d6a05ee161597380346a6cd11d71f0fa58d5ddb480a528e85e70bdb55904af8253b3a051e8cb9a583463217423146de2503fcba6be0c21cd624456bf830a6f8789e93ea02d2049a1a43b4c1409793f8fd21181fadc3f5d645909998876227f7f3d4f8fa6a0a9dc17c0c91876c9183b348321bdf025e2f6f0e087c6d1b7941635f1db314226a0941655b6277d7ae3573ad038f87bd135fb7c385ab2e07b214d3e6d6e261c8b65a0bc634e3ad0d3010f8dfbfacd2e10198e7c814d30d40e07987b24c36aea3c428f80","0xf8b1a0df5900ec8abdb023b4ededf5ca973bb8fdffeaf4fff45bdee6821e2177fb9be3a0996dbe53744140b7f467c72ef93d107539d783922fc78c3e9dc0ec1bd05788db8080a0dc3910d1aea67675f479f2cd95f6f15bb02e8935805f1cce951bbc9134901f4580a0cf56a435fe6b8cc75faf566d7e9767d219650723dad3eb7aa3f4743feaf5e4b880a0d78ebfe5f7c2ea4bb7a89bf465c7a308386a474fc3176b10ef039ab52747cc728080808080808080","0xf8518080808080808080808080a04d046e6057422dde202a8394ed7f71b4c92b776c2eb51d976ca71ecf41db1b7e808080a036698dc604cca461696b339fabf922f3e5898571f81bf3bfe96d897e21f8a99880","0xf8689f364b9c7b69139bea764e6a6ed3394a2fb0c3affd66fe531a68eaeca9cfe297b846f8440180a06eefedf8b895defe8b8b32522a7746b9c388b67cc710ec0aaa45c2305fb9cedfa0c09715ef7e413bd06144c8c6dd476b1901eb2e29c6826f3c7a2b2e1834887c0a"];
+    pub(crate) const GANACHE_STORAGE_PROOF: [&str; 1] = ["0xf844a1201663f081233a2f6d2dc07d9801a0a4bd2608df182782575baee276e196bad7aea1a034607c9bbfeb9c23509680f04363f298fdb0b5f9abe327304ecd1daca08cda9c"];
+
+    pub(crate) fn decode_proof(proof: &[&str]) -> Vec<Vec<u8>> {
+        proof
+            .iter()
+            .map(|p| hex::decode(&p[2..]).unwrap())
+            .collect()
+    }
+
     #[test]
     fn test_verify_commitment() {
         // Test with eth_getProof result from ganache.
-        let account_proof = ["0xf90211a021ff4e4e9ef3e4206823799dc4181bef914f590200c1ba58d8b517ec2ec902e9a0fa22b58ff107979c4ecb0d18dcc4c9c4a21d82a5f49e18bc84a6dcc1f43c6cffa05f401c8ccf28c88c795c8dc3194c10de0364f121365be2f81c89ae9a4466ac4aa07d55b967ed900e13b3dd0794dd7284d6a84b6aeb0da2d0c22ae3c1d46206e51ba0438cfa73f409f90f93f88859ba2a249b158341547c715c9ea05863f25e0d872fa02245741cb87ce55bef07c0dded7d132b406a961a3876d3126a5c770902097551a04c5a9fe5ecc0f2400e2ea6eeae97bbbed17e40e2c95bc60044f4f8bd7d502049a07f0773a09800a67a39a15b889e2f777caffab9cbd7d44e7749f92ea78b4ab188a050e2407b752610686f21766779ef5184561d1280387ad40e190429fccc9dc1bca0ea55baaf73e67e8d7bf88847ef7ed8d11cfc1731174555a9fb9092e704e4b9d6a05ee161597380346a6cd11d71f0fa58d5ddb480a528e85e70bdb55904af8253b3a051e8cb9a583463217423146de2503fcba6be0c21cd624456bf830a6f8789e93ea02d2049a1a43b4c1409793f8fd21181fadc3f5d645909998876227f7f3d4f8fa6a0a9dc17c0c91876c9183b348321bdf025e2f6f0e087c6d1b7941635f1db314226a0941655b6277d7ae3573ad038f87bd135fb7c385ab2e07b214d3e6d6e261c8b65a0bc634e3ad0d3010f8dfbfacd2e10198e7c814d30d40e07987b24c36aea3c428f80","0xf8b1a0df5900ec8abdb023b4ededf5ca973bb8fdffeaf4fff45bdee6821e2177fb9be3a0996dbe53744140b7f467c72ef93d107539d783922fc78c3e9dc0ec1bd05788db8080a0dc3910d1aea67675f479f2cd95f6f15bb02e8935805f1cce951bbc9134901f4580a0cf56a435fe6b8cc75faf566d7e9767d219650723dad3eb7aa3f4743feaf5e4b880a0d78ebfe5f7c2ea4bb7a89bf465c7a308386a474fc3176b10ef039ab52747cc728080808080808080","0xf8518080808080808080808080a04d046e6057422dde202a8394ed7f71b4c92b776c2eb51d976ca71ecf41db1b7e808080a036698dc604cca461696b339fabf922f3e5898571f81bf3bfe96d897e21f8a99880","0xf8689f364b9c7b69139bea764e6a6ed3394a2fb0c3affd66fe531a68eaeca9cfe297b846f8440180a06eefedf8b895defe8b8b32522a7746b9c388b67cc710ec0aaa45c2305fb9cedfa0c09715ef7e413bd06144c8c6dd476b1901eb2e29c6826f3c7a2b2e1834887c0a"];
         verify_account_and_storage(
-            &hex::decode("b05361ee4e2433d107e7bbd512d906b0b9cb9b7122636dff7fdb74f78c16f551").unwrap(),
-            &hex::decode("1C6e2aAcAf61711A2dD74d18363766482d93CF84").unwrap(),
-            &Vec::from_iter(account_proof.iter().map(|p| hex::decode(&p[2..]).unwrap())),
+            &hex::decode(GANACHE_STATE_ROOT).unwrap(),
+            &hex::decode(GANACHE_IBC_HANDLER_ADDRESS).unwrap(),
+            &decode_proof(&GANACHE_ACCOUNT_PROOF),
             commitment_slot(b"abc"),
             keccak256(b"def"),
-            &[hex::decode("f844a1201663f081233a2f6d2dc07d9801a0a4bd2608df182782575baee276e196bad7aea1a034607c9bbfeb9c23509680f04363f298fdb0b5f9abe327304ecd1daca08cda9c").unwrap()],
+            &decode_proof(&GANACHE_STORAGE_PROOF),
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_verify_mpt_multiproof_missing_node() {
+        // Root node is not in the proof.
+        let root = keccak256(b"root");
+        assert!(verify_mpt_multiproof(&root, &[b"abc"], &[b"def"]).is_err());
+    }
 }
@@ -1,7 +1,21 @@
-//! Functions for commitment paths.
+//! Functions for commitment paths and values.
 
 use alloc::string::String;
 
+use crate::object::Packet;
+use crate::proto::client::Height;
+
+pub fn client_state_path(client_id: &str) -> String {
+    format!("clients/{client_id}/clientState")
+}
+
+pub fn consensus_state_path(client_id: &str, height: Height) -> String {
+    format!(
+        "clients/{client_id}/consensusStates/{}-{}",
+        height.revision_number, height.revision_height
+    )
+}
+
 pub fn connection_path(connection_id: &str) -> String {
     format!("connections/{connection_id}")
 }
@@ -18,6 +32,128 @@ pub fn packet_acknowledgement_commitment_path(
     format!("acks/ports/{port_id}/channels/{channel_id}/sequences/{sequence}")
 }
 
+pub fn packet_receipt_path(port_id: &str, channel_id: &str, sequence: u64) -> String {
+    format!("receipts/ports/{port_id}/channels/{channel_id}/sequences/{sequence}")
+}
+
 pub fn channel_path(port_id: &str, channel_id: &str) -> String {
     format!("channelEnds/ports/{port_id}/channels/{channel_id}")
 }
+
+/// How packet and acknowledgement commitment values are computed. Both sides
+/// of a connection must use the same scheme.
+pub trait CommitmentScheme {
+    fn packet_commitment(packet: &Packet) -> [u8; 32];
+
+    fn ack_commitment(ack: &[u8]) -> [u8; 32];
+}
+
+/// `CommitPacket` and `CommitAcknowledgement` of ibc-go, for Cosmos chains.
+/// Integers are big-endian.
+pub struct IbcGo;
+
+impl CommitmentScheme for IbcGo {
+    fn packet_commitment(packet: &Packet) -> [u8; 32] {
+        sha256(&[
+            &packet.timeout_timestamp.to_be_bytes(),
+            &packet.timeout_height.revision_number.to_be_bytes(),
+            &packet.timeout_height.revision_height.to_be_bytes(),
+            &sha256(&[&packet.data]),
+        ])
+    }
+
+    fn ack_commitment(ack: &[u8]) -> [u8; 32] {
+        sha256(&[ack])
+    }
+}
+
+/// The scheme of the ibc-solidity handler contract on Axon. Integers are
+/// little-endian.
+pub struct IbcSolidity;
+
+impl CommitmentScheme for IbcSolidity {
+    fn packet_commitment(packet: &Packet) -> [u8; 32] {
+        sha256(&[
+            &packet.timeout_timestamp.to_le_bytes(),
+            &packet.timeout_height.revision_number.to_le_bytes(),
+            &packet.timeout_height.revision_height.to_le_bytes(),
+            &sha256(&[&packet.data]),
+        ])
+    }
+
+    fn ack_commitment(ack: &[u8]) -> [u8; 32] {
+        sha256(&[ack])
+    }
+}
+
+/// Scheme used by the handlers, selected by the `ibc-go-commitment` feature.
+#[cfg(feature = "ibc-go-commitment")]
+pub type DefaultCommitmentScheme = IbcGo;
+#[cfg(not(feature = "ibc-go-commitment"))]
+pub type DefaultCommitmentScheme = IbcSolidity;
+
+pub(crate) fn sha256(msgs: &[&[u8]]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    for m in msgs {
+        hasher.update(m);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::client::Height;
+
+    fn packet() -> Packet {
+        Packet {
+            data: b"hello".to_vec(),
+            timeout_height: Height {
+                revision_number: 1,
+                revision_height: 100,
+            },
+            timeout_timestamp: 1_700_000_000_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    // Vectors are computed independently with Python's hashlib and struct.
+    #[test]
+    fn test_ibc_go_vectors() {
+        assert_eq!(
+            hex::encode(IbcGo::packet_commitment(&packet())),
+            "512d19e8d8bdadc538c6e1e13e11b7019aa57be7d15c9c15871e02a51dfd1125"
+        );
+        assert_eq!(
+            hex::encode(IbcGo::ack_commitment(&[1])),
+            "4bf5122f344554c53bde2ebb8cd2b7e3d1600ad631c385a5d7cce23c7785459a"
+        );
+    }
+
+    #[test]
+    fn test_ibc_solidity_vectors() {
+        assert_eq!(
+            hex::encode(IbcSolidity::packet_commitment(&packet())),
+            "b8ef3baa38a1e85326c608c193c669fb8be959ff650017b41efa28150e9ebf63"
+        );
+        assert_eq!(
+            hex::encode(IbcSolidity::ack_commitment(&[1])),
+            "4bf5122f344554c53bde2ebb8cd2b7e3d1600ad631c385a5d7cce23c7785459a"
+        );
+    }
+
+    #[test]
+    fn test_revision_number_is_committed() {
+        let mut other = packet();
+        other.timeout_height.revision_number = 2;
+        assert_ne!(
+            IbcGo::packet_commitment(&packet()),
+            IbcGo::packet_commitment(&other)
+        );
+        assert_ne!(
+            IbcSolidity::packet_commitment(&packet()),
+            IbcSolidity::packet_commitment(&other)
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Light client of EVM chains running ibc-solidity.
+//!
+//! Consensus of the EVM chain is not verified here. Block hashes of trusted
+//! headers, e.g. from a client cell that follows the chain with sync
+//! committee updates, are given to the client. Other headers can be trusted
+//! by linking them to a trusted header by parent hash. Commitments are then
+//! verified with account and storage proofs against state roots of trusted
+//! headers, the same way as `AxonClient`.
+
+use alloc::vec::Vec;
+use ethereum_types::H256;
+use rlp::Rlp;
+use rlp_derive::{RlpDecodable, RlpEncodable};
+
+use crate::axon_client::slot::CommitmentSlotLayout;
+use crate::axon_client::{keccak256, verify, ProofNode};
+use crate::handler::Client;
+use crate::object::VerifyError;
+use crate::proto::client::Height;
+
+#[derive(RlpDecodable, RlpEncodable)]
+pub struct EthCommitmentProof {
+    /// RLP encoded block header.
+    pub header: Vec<u8>,
+    pub account_proof: Vec<ProofNode>,
+    pub storage_proof: Vec<ProofNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, RlpDecodable, RlpEncodable)]
+pub struct TrustedHeader {
+    pub number: u64,
+    pub hash: H256,
+}
+
+/// Fields of an RLP encoded block header used by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthHeader {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub state_root: H256,
+    pub number: u64,
+}
+
+impl EthHeader {
+    pub fn decode(header: &[u8]) -> Result<Self, VerifyError> {
+        let rlp = Rlp::new(header);
+        // parent_hash, ommers_hash, beneficiary, state_root, transactions_root,
+        // receipts_root, logs_bloom, difficulty, number, gas_limit, gas_used,
+        // timestamp, extra_data, mix_hash, nonce, and fields added by forks.
+        if rlp.item_count()? < 15 {
+            return Err(VerifyError::SerdeError);
+        }
+        Ok(Self {
+            hash: keccak256(header).into(),
+            parent_hash: hash_at(&rlp, 0)?,
+            state_root: hash_at(&rlp, 3)?,
+            number: rlp.val_at(8)?,
+        })
+    }
+}
+
+fn hash_at(rlp: &Rlp, index: usize) -> Result<H256, VerifyError> {
+    let data = rlp.at(index)?.data()?;
+    if data.len() != 32 {
+        return Err(VerifyError::SerdeError);
+    }
+    Ok(H256::from_slice(data))
+}
+
+#[derive(Default)]
+pub struct EthClient {
+    pub ibc_handler_address: [u8; 20],
+    /// Ordered by block number, without duplicate numbers.
+    trusted_headers: Vec<TrustedHeader>,
+    /// Storage layout of commitments in the IBC handler contract.
+    pub slot_layout: CommitmentSlotLayout,
+}
+
+impl EthClient {
+    /// Client trusting `trusted_headers`, in any order. Headers with the same
+    /// number are rejected.
+    pub fn new(
+        ibc_handler_address: [u8; 20],
+        mut trusted_headers: Vec<TrustedHeader>,
+    ) -> Result<Self, VerifyError> {
+        if ibc_handler_address == [0; 20] {
+            return Err(VerifyError::ZeroIBCHandlerAddress);
+        }
+        trusted_headers.sort_by_key(|h| h.number);
+        if trusted_headers
+            .windows(2)
+            .any(|w| matches!(w, [a, b] if a.number == b.number))
+        {
+            return Err(VerifyError::UntrustedHeader);
+        }
+        Ok(Self {
+            ibc_handler_address,
+            trusted_headers,
+            slot_layout: CommitmentSlotLayout::default(),
+        })
+    }
+
+    /// Use another storage layout of commitments, for IBC handler contracts
+    /// other than ibc-solidity.
+    pub fn with_slot_layout(mut self, slot_layout: CommitmentSlotLayout) -> Self {
+        self.slot_layout = slot_layout;
+        self
+    }
+
+    /// Trusted headers, ordered by block number.
+    pub fn trusted_headers(&self) -> &[TrustedHeader] {
+        &self.trusted_headers
+    }
+
+    pub fn trusted_header(&self, number: u64) -> Option<&TrustedHeader> {
+        let idx = self
+            .trusted_headers
+            .binary_search_by_key(&number, |h| h.number)
+            .ok()?;
+        self.trusted_headers.get(idx)
+    }
+
+    /// Trust RLP encoded headers that extend the latest trusted header.
+    ///
+    /// Each header must be the child of the previous one, and the first must
+    /// be the child of the latest trusted header. Either all headers are
+    /// trusted or, on error, none of them.
+    pub fn extend_trusted_headers<T: AsRef<[u8]>>(
+        &mut self,
+        headers: &[T],
+    ) -> Result<(), VerifyError> {
+        let mut latest = *self
+            .trusted_headers
+            .last()
+            .ok_or(VerifyError::UntrustedHeader)?;
+        let mut linked = Vec::with_capacity(headers.len());
+        for header in headers {
+            let header = EthHeader::decode(header.as_ref())?;
+            if header.parent_hash != latest.hash
+                || Some(header.number) != latest.number.checked_add(1)
+            {
+                return Err(VerifyError::UntrustedHeader);
+            }
+            latest = TrustedHeader {
+                number: header.number,
+                hash: header.hash,
+            };
+            linked.push(latest);
+        }
+        self.trusted_headers.extend(linked);
+        Ok(())
+    }
+
+    /// Verify that the RLP encoded header is trusted and at `height`.
+    fn verify_header(&self, height: Height, header: &[u8]) -> Result<EthHeader, VerifyError> {
+        let header = EthHeader::decode(header)?;
+        self.validate_proof_height(height)?;
+        if height.revision_height != header.number {
+            return Err(VerifyError::Mpt);
+        }
+        match self.trusted_header(header.number) {
+            Some(trusted) if trusted.hash == header.hash => Ok(header),
+            _ => Err(VerifyError::UntrustedHeader),
+        }
+    }
+}
+
+impl Client for EthClient {
+    /// Ethereum chains have no revisions, so heights are on revision 0.
+    fn latest_height(&self) -> Option<Height> {
+        let latest = self.trusted_headers.last()?;
+        Some(Height {
+            revision_number: 0,
+            revision_height: latest.number,
+        })
+    }
+
+    fn verify_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+        value: &[u8],
+    ) -> Result<(), VerifyError> {
+        self.verify_slot_value(height, proof, path, keccak256(value))
+    }
+
+    fn verify_non_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError> {
+        self.verify_slot_value(height, proof, path, [0; 32])
+    }
+}
+
+impl EthClient {
+    /// Verify that the storage slot of `path` has `slot_value`, zero if it
+    /// doesn't exist.
+    fn verify_slot_value(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+        slot_value: [u8; 32],
+    ) -> Result<(), VerifyError> {
+        let EthCommitmentProof {
+            header,
+            account_proof,
+            storage_proof,
+        } = rlp::decode(proof).map_err(|_| VerifyError::SerdeError)?;
+
+        let header = self.verify_header(height, &header)?;
+
+        verify::verify_account_and_storage(
+            header.state_root.as_bytes(),
+            &self.ibc_handler_address,
+            &account_proof,
+            self.slot_layout.slot(path),
+            slot_value,
+            &storage_proof,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axon_client::verify::tests::*;
+    use crate::testing::eth_header;
+
+    fn ganache_client() -> (EthClient, Vec<u8>) {
+        let state_root = H256::from_slice(&hex::decode(GANACHE_STATE_ROOT).unwrap());
+        let header = eth_header(H256::zero(), state_root, 7);
+        let address = hex::decode(GANACHE_IBC_HANDLER_ADDRESS).unwrap();
+        let client = EthClient::new(
+            address.try_into().unwrap(),
+            vec![TrustedHeader {
+                number: 7,
+                hash: keccak256(&header).into(),
+            }],
+        )
+        .unwrap();
+        (client, header)
+    }
+
+    fn ganache_proof(header: Vec<u8>) -> Vec<u8> {
+        rlp::encode(&EthCommitmentProof {
+            header,
+            account_proof: decode_proof(&GANACHE_ACCOUNT_PROOF),
+            storage_proof: decode_proof(&GANACHE_STORAGE_PROOF),
+        })
+        .to_vec()
+    }
+
+    fn height(revision_height: u64) -> Height {
+        Height {
+            revision_number: 0,
+            revision_height,
+        }
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let (client, header) = ganache_client();
+        let proof = ganache_proof(header);
+        client
+            .verify_membership(height(7), &proof, b"abc", b"def")
+            .unwrap();
+        assert!(client
+            .verify_membership(height(7), &proof, b"abc", b"xyz")
+            .is_err());
+        assert!(client
+            .verify_membership(height(8), &proof, b"abc", b"def")
+            .is_err());
+    }
+
+    #[test]
+    fn test_untrusted_header() {
+        let (client, _) = ganache_client();
+        let state_root = H256::from_slice(&hex::decode(GANACHE_STATE_ROOT).unwrap());
+        // Same state root, but a different header.
+        let proof = ganache_proof(eth_header(H256::repeat_byte(1), state_root, 7));
+        assert!(matches!(
+            client.verify_membership(height(7), &proof, b"abc", b"def"),
+            Err(VerifyError::UntrustedHeader)
+        ));
+    }
+
+    #[test]
+    fn test_proof_height() {
+        let (client, header) = ganache_client();
+        let proof = ganache_proof(header);
+        assert_eq!(client.latest_height(), Some(height(7)));
+        assert!(matches!(
+            client.verify_membership(height(8), &proof, b"abc", b"def"),
+            Err(VerifyError::InvalidProofHeight)
+        ));
+        let other_revision = Height {
+            revision_number: 1,
+            revision_height: 7,
+        };
+        assert!(matches!(
+            client.verify_membership(other_revision, &proof, b"abc", b"def"),
+            Err(VerifyError::InvalidProofHeight)
+        ));
+    }
+
+    #[test]
+    fn test_extend_trusted_headers() {
+        let (mut client, header_7) = ganache_client();
+        let header_8 = eth_header(keccak256(&header_7).into(), H256::zero(), 8);
+        let header_9 = eth_header(keccak256(&header_8).into(), H256::zero(), 9);
+        // Not linked to a trusted header.
+        assert!(matches!(
+            client.extend_trusted_headers(&[&header_9]),
+            Err(VerifyError::UntrustedHeader)
+        ));
+
+        client
+            .extend_trusted_headers(&[&header_8, &header_9])
+            .unwrap();
+        assert_eq!(
+            client.trusted_header(9).unwrap().hash,
+            H256::from(keccak256(&header_9))
+        );
+    }
+
+    #[test]
+    fn test_extend_trusted_headers_atomic() {
+        let (mut client, header_7) = ganache_client();
+        let header_8 = eth_header(keccak256(&header_7).into(), H256::zero(), 8);
+        let header_9 = eth_header(keccak256(&header_8).into(), H256::zero(), 9);
+        let bad_header_9 = eth_header(H256::repeat_byte(1), H256::zero(), 9);
+        let header_10 = eth_header(keccak256(&header_9).into(), H256::zero(), 10);
+        let trusted = client.trusted_headers().to_vec();
+
+        assert!(matches!(
+            client.extend_trusted_headers(&[&header_8, &bad_header_9, &header_10]),
+            Err(VerifyError::UntrustedHeader)
+        ));
+        assert_eq!(client.trusted_headers(), trusted);
+
+        // The same batch with the bad header fixed can be retried.
+        client
+            .extend_trusted_headers(&[&header_8, &header_9, &header_10])
+            .unwrap();
+        assert_eq!(client.latest_height(), Some(height(10)));
+    }
+
+    #[test]
+    fn test_duplicate_trusted_headers() {
+        let address = hex::decode(GANACHE_IBC_HANDLER_ADDRESS).unwrap();
+        let headers = vec![
+            TrustedHeader {
+                number: 8,
+                hash: H256::repeat_byte(1),
+            },
+            TrustedHeader {
+                number: 7,
+                hash: H256::repeat_byte(2),
+            },
+            TrustedHeader {
+                number: 8,
+                hash: H256::repeat_byte(3),
+            },
+        ];
+        assert!(matches!(
+            EthClient::new(address.try_into().unwrap(), headers),
+            Err(VerifyError::UntrustedHeader)
+        ));
+    }
+}
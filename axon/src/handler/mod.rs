@@ -1,13 +1,15 @@
 #![allow(clippy::too_many_arguments)]
 
 use alloc::string::ToString;
+use core::str::FromStr;
 use prost::Message;
 
 use crate::consts::COMMITMENT_PREFIX;
+use crate::host::{ChannelId, ClientId, ConnectionId, PortId};
 use crate::message::{
     MsgAckPacket, MsgChannelCloseConfirm, MsgChannelOpenAck, MsgChannelOpenConfirm,
     MsgChannelOpenTry, MsgConnectionOpenAck, MsgConnectionOpenConfirm, MsgConnectionOpenTry,
-    MsgRecvPacket,
+    MsgRecvPacket, MsgTimeoutPacket,
 };
 use crate::object::{ConnectionEnd, Ordering, State, VerifyError, Version};
 use crate::proto::client::Height;
@@ -20,6 +22,16 @@ mod test;
 
 pub use objects::*;
 
+/// Reject proofs of a client that is frozen or expired, and proofs at
+/// heights the client can't verify.
+fn check_client(client: &impl Client, proof_height: Height) -> Result<(), VerifyError> {
+    match client.status() {
+        ClientStatus::Active => client.validate_proof_height(proof_height),
+        ClientStatus::Frozen => Err(VerifyError::ClientFrozen),
+        ClientStatus::Expired => Err(VerifyError::ClientExpired),
+    }
+}
+
 pub fn handle_msg_connection_open_init(
     mut old_connections: IbcConnections,
     old_args: ConnectionArgs,
@@ -39,6 +51,7 @@ pub fn handle_msg_connection_open_init(
     if !new.counterparty.connection_id.is_empty() {
         return Err(VerifyError::WrongConnectionState);
     }
+    ClientId::from_str(&new.counterparty.client_id)?;
 
     if new.versions != [Version::version_1()] {
         return Err(VerifyError::WrongConnectionState);
@@ -68,6 +81,7 @@ pub fn handle_msg_connection_open_init(
 
 pub fn handle_msg_connection_open_try<C: Client>(
     client: C,
+    self_client: impl ValidateSelfClient,
     mut old_connections: IbcConnections,
     old_args: ConnectionArgs,
     new_connections: IbcConnections,
@@ -75,6 +89,8 @@ pub fn handle_msg_connection_open_try<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgConnectionOpenTry,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_args != new_args {
         return Err(VerifyError::WrongConnectionArgs);
     }
@@ -85,6 +101,8 @@ pub fn handle_msg_connection_open_try<C: Client>(
         .ok_or(VerifyError::WrongConnectionState)?;
 
     let counterparty = &connection.counterparty;
+    let counterparty_client_id = ClientId::from_str(&counterparty.client_id)?;
+    let counterparty_connection_id = ConnectionId::from_str(&counterparty.connection_id)?;
 
     old_connections.connections.push(ConnectionEnd {
         state: State::OpenTry,
@@ -107,7 +125,7 @@ pub fn handle_msg_connection_open_try<C: Client>(
 
     let expected_connection_end_on_counterparty = proto::connection::ConnectionEnd {
         state: proto::connection::State::Init as _,
-        client_id: counterparty.client_id.clone(),
+        client_id: counterparty_client_id.clone().into(),
         counterparty: Some(proto::connection::Counterparty {
             client_id,
             connection_id: "".to_string(),
@@ -123,12 +141,21 @@ pub fn handle_msg_connection_open_try<C: Client>(
         &client,
         msg.proof_height,
         &msg.proof_init,
-        &counterparty.connection_id,
+        counterparty_connection_id.as_str(),
         &expected_connection_end_on_counterparty,
     )?;
 
-    // TODO: verify client and consensus.
-    Ok(())
+    verify_self_client(
+        &client,
+        &self_client,
+        msg.proof_height,
+        counterparty_client_id.as_str(),
+        &msg.client_state_of_b_on_a,
+        &msg.proof_client_state_of_b_on_a,
+        msg.consensus_height_of_b_on_a,
+        &msg.consensus_state_of_b_on_a,
+        &msg.proof_consensus_state_of_b_on_a,
+    )
 }
 
 fn write_connection_state(
@@ -154,8 +181,39 @@ fn verify_connection_state(
     )
 }
 
+/// Validate the counterparty's client of this chain, and verify that the
+/// counterparty has this client and consensus state.
+fn verify_self_client(
+    client: &impl Client,
+    self_client: &impl ValidateSelfClient,
+    proof_height: Height,
+    counterparty_client_id: &str,
+    client_state: &[u8],
+    proof_client_state: &[u8],
+    consensus_height: Height,
+    consensus_state: &[u8],
+    proof_consensus_state: &[u8],
+) -> Result<(), VerifyError> {
+    self_client.validate_self_client(client_state)?;
+    self_client.validate_self_consensus_state(consensus_height, consensus_state)?;
+
+    client.verify_membership(
+        proof_height,
+        proof_client_state,
+        client_state_path(counterparty_client_id).as_bytes(),
+        client_state,
+    )?;
+    client.verify_membership(
+        proof_height,
+        proof_consensus_state,
+        consensus_state_path(counterparty_client_id, consensus_height).as_bytes(),
+        consensus_state,
+    )
+}
+
 pub fn handle_msg_connection_open_ack<C: Client>(
     client: C,
+    self_client: impl ValidateSelfClient,
     mut old: IbcConnections,
     old_args: ConnectionArgs,
     new: IbcConnections,
@@ -163,14 +221,22 @@ pub fn handle_msg_connection_open_ack<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgConnectionOpenAck,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_args != new_args {
         return Err(VerifyError::WrongConnectionArgs);
     }
 
     // Verify connection state transition.
     let conn_idx = msg.conn_id_on_a;
-    let old_connection = &mut old.connections[conn_idx];
-    let new_connection = &new.connections[conn_idx];
+    let old_connection = old
+        .connections
+        .get_mut(conn_idx)
+        .ok_or(VerifyError::WrongConnectionId)?;
+    let new_connection = new
+        .connections
+        .get(conn_idx)
+        .ok_or(VerifyError::WrongConnectionId)?;
     if old_connection.state != State::Init {
         return Err(VerifyError::WrongConnectionState);
     }
@@ -179,6 +245,9 @@ pub fn handle_msg_connection_open_ack<C: Client>(
     if old != new {
         return Err(VerifyError::WrongConnectionState);
     }
+    let counterparty_client_id = ClientId::from_str(&new_connection.counterparty.client_id)?;
+    let counterparty_connection_id =
+        ConnectionId::from_str(&new_connection.counterparty.connection_id)?;
 
     let client_id = new_args.client_id();
     let connection_id = connection_id(&client_id, conn_idx);
@@ -192,7 +261,7 @@ pub fn handle_msg_connection_open_ack<C: Client>(
     // Verify counterparty connection state.
     let expected = proto::connection::ConnectionEnd {
         state: proto::connection::State::Tryopen as _,
-        client_id: new_connection.counterparty.client_id.clone(),
+        client_id: counterparty_client_id.clone().into(),
         counterparty: Some(proto::connection::Counterparty {
             connection_id,
             client_id,
@@ -207,12 +276,21 @@ pub fn handle_msg_connection_open_ack<C: Client>(
         &client,
         msg.proof_height,
         &msg.proof_try,
-        &new_connection.counterparty.connection_id,
+        counterparty_connection_id.as_str(),
         &expected,
     )?;
 
-    // TODO: verify client.
-    Ok(())
+    verify_self_client(
+        &client,
+        &self_client,
+        msg.proof_height,
+        counterparty_client_id.as_str(),
+        &msg.client_state_of_a_on_b,
+        &msg.proof_client_state_of_a_on_b,
+        msg.consensus_height_of_a_on_b,
+        &msg.consensus_state_of_a_on_b,
+        &msg.proof_consensus_state_of_a_on_b,
+    )
 }
 
 pub fn handle_msg_connection_open_confirm<C: Client>(
@@ -224,14 +302,22 @@ pub fn handle_msg_connection_open_confirm<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgConnectionOpenConfirm,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_args != new_args {
         return Err(VerifyError::WrongConnectionArgs);
     }
 
     // Verify state transition.
     let conn_idx = msg.conn_id_on_b;
-    let old_connection = &mut old.connections[conn_idx];
-    let new_connection = &new.connections[conn_idx];
+    let old_connection = old
+        .connections
+        .get_mut(conn_idx)
+        .ok_or(VerifyError::WrongConnectionId)?;
+    let new_connection = new
+        .connections
+        .get(conn_idx)
+        .ok_or(VerifyError::WrongConnectionId)?;
     if old_connection.state != State::OpenTry {
         return Err(VerifyError::WrongConnectionState);
     }
@@ -239,6 +325,8 @@ pub fn handle_msg_connection_open_confirm<C: Client>(
     if old != new {
         return Err(VerifyError::WrongConnectionState);
     }
+    let counterparty_connection_id =
+        ConnectionId::from_str(&new_connection.counterparty.connection_id)?;
 
     let client_id = new_args.client_id();
     let connection_id = connection_id(&client_id, conn_idx);
@@ -268,7 +356,7 @@ pub fn handle_msg_connection_open_confirm<C: Client>(
         &client,
         msg.proof_height,
         &msg.proof_ack,
-        &new_connection.counterparty.connection_id,
+        counterparty_connection_id.as_str(),
         &expected,
     )?;
 
@@ -287,7 +375,10 @@ pub fn handle_msg_channel_open_init(
     if channel_args.channel_id != old_connections.next_channel_number {
         return Err(VerifyError::WrongChannel);
     }
-    old_connections.next_channel_number += 1;
+    old_connections.next_channel_number = old_connections
+        .next_channel_number
+        .checked_add(1)
+        .ok_or(VerifyError::WrongChannel)?;
     if old_connections != new_connections {
         return Err(VerifyError::WrongConnectionState);
     }
@@ -302,17 +393,19 @@ pub fn handle_msg_channel_open_init(
 
     let new = channel;
 
-    if new.number != channel_args.channel_id || new.port_id != channel_args.port_id_str() {
+    if new.number != channel_args.channel_id
+        || PortId::from_str(&new.port_id)? != PortId::from_bytes(&channel_args.port_id)
+    {
         return Err(VerifyError::WrongChannel);
     }
 
     let client_id = new_connection_args.client_id();
 
-    if new.connection_hops.len() != 1 {
+    let [connection_hop] = new.connection_hops.as_slice() else {
         return Err(VerifyError::ConnectionsWrong);
-    }
+    };
     let conn = new_connections
-        .get_by_id(&client_id, &new.connection_hops[0])
+        .get_by_id(&client_id, connection_hop)
         .ok_or(VerifyError::WrongConnectionId)?;
 
     if conn.state != State::Open {
@@ -322,6 +415,7 @@ pub fn handle_msg_channel_open_init(
     if new.state != State::Init || !new.counterparty.channel_id.is_empty() {
         return Err(VerifyError::WrongChannelState);
     }
+    PortId::from_str(&new.counterparty.port_id)?;
 
     if new.sequence != Sequence::default() {
         return Err(VerifyError::WrongPacketSequence);
@@ -350,10 +444,15 @@ pub fn handle_msg_channel_open_try<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgChannelOpenTry,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if channel_args.channel_id != old_connections.next_channel_number {
         return Err(VerifyError::WrongChannel);
     }
-    old_connections.next_channel_number += 1;
+    old_connections.next_channel_number = old_connections
+        .next_channel_number
+        .checked_add(1)
+        .ok_or(VerifyError::WrongChannel)?;
     if old_connections != new_connections {
         return Err(VerifyError::WrongConnectionState);
     }
@@ -368,18 +467,20 @@ pub fn handle_msg_channel_open_try<C: Client>(
 
     let new = channel;
 
-    if new.number != channel_args.channel_id || new.port_id != channel_args.port_id_str() {
+    if new.number != channel_args.channel_id
+        || PortId::from_str(&new.port_id)? != PortId::from_bytes(&channel_args.port_id)
+    {
         return Err(VerifyError::WrongChannel);
     }
 
     let client_id = new_connection_args.client_id();
 
-    if new.connection_hops.len() != 1 {
+    let [connection_hop] = new.connection_hops.as_slice() else {
         return Err(VerifyError::ConnectionsWrong);
-    }
+    };
 
     let conn = new_connections
-        .get_by_id(&client_id, &new.connection_hops[0])
+        .get_by_id(&client_id, connection_hop)
         .ok_or(VerifyError::WrongConnectionId)?;
 
     if conn.state != State::Open {
@@ -389,6 +490,8 @@ pub fn handle_msg_channel_open_try<C: Client>(
     if new.state != State::OpenTry {
         return Err(VerifyError::WrongChannelState);
     }
+    PortId::from_str(&new.counterparty.port_id)?;
+    ChannelId::from_str(&new.counterparty.channel_id)?;
 
     if new.sequence != Sequence::default() {
         return Err(VerifyError::WrongPacketSequence);
@@ -423,7 +526,7 @@ pub fn handle_msg_channel_open_try<C: Client>(
     verify_channel_state(
         &client,
         msg.proof_height,
-        &msg.proof_init[..],
+        &msg.proof_init,
         &new.counterparty.port_id,
         &new.counterparty.channel_id,
         &expected,
@@ -466,6 +569,8 @@ pub fn handle_msg_channel_open_ack<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgChannelOpenAck,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_args.open {
         return Err(VerifyError::WrongChannelArgs);
     }
@@ -485,6 +590,7 @@ pub fn handle_msg_channel_open_ack<C: Client>(
     if old != new {
         return Err(VerifyError::WrongChannel);
     }
+    ChannelId::from_str(&new.counterparty.channel_id)?;
 
     let port_id = new.port_id.clone();
     let channel_id = new_args.channel_id_str();
@@ -523,6 +629,8 @@ pub fn handle_msg_channel_open_confirm<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgChannelOpenConfirm,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_args.open {
         return Err(VerifyError::WrongChannelArgs);
     }
@@ -609,6 +717,8 @@ pub fn handle_msg_channel_close_confirm<C: Client>(
     commitment: impl WriteOrVerifyCommitments,
     msg: MsgChannelCloseConfirm,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old.state != State::Open {
         return Err(VerifyError::WrongChannelState);
     }
@@ -664,7 +774,11 @@ pub fn handle_msg_send_packet(
         return Err(VerifyError::WrongPacketSequence);
     }
 
-    old_channel.sequence.next_sequence_sends += 1;
+    old_channel.sequence.next_sequence_sends = old_channel
+        .sequence
+        .next_sequence_sends
+        .checked_add(1)
+        .ok_or(VerifyError::WrongPacketSequence)?;
     if old_channel != new_channel {
         return Err(VerifyError::WrongChannel);
     }
@@ -710,13 +824,7 @@ pub fn handle_msg_send_packet(
             &ibc_packet.packet.source_channel_id,
             ibc_packet.packet.sequence,
         ),
-        sha256(&[
-            &ibc_packet.packet.timeout_timestamp.to_le_bytes(),
-            // Revision number
-            &0u64.to_le_bytes(),
-            &ibc_packet.packet.timeout_height.to_le_bytes(),
-            &sha256(&[&ibc_packet.packet.data]),
-        ]),
+        DefaultCommitmentScheme::packet_commitment(&ibc_packet.packet),
     )])?;
 
     Ok(())
@@ -734,6 +842,8 @@ pub fn handle_msg_recv_packet<C: Client>(
     mut commitment: impl WriteOrVerifyCommitments,
     msg: MsgRecvPacket,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     // A write_ack packet can be consumed.
     if let Some(ibc_packed) = useless_ibc_packet {
         if ibc_packed.status != PacketStatus::WriteAck {
@@ -749,7 +859,11 @@ pub fn handle_msg_recv_packet<C: Client>(
         if old_channel.sequence.next_sequence_recvs != ibc_packet.packet.sequence {
             return Err(VerifyError::WrongPacketSequence);
         }
-        old_channel.sequence.next_sequence_recvs += 1;
+        old_channel.sequence.next_sequence_recvs = old_channel
+            .sequence
+            .next_sequence_recvs
+            .checked_add(1)
+            .ok_or(VerifyError::WrongPacketSequence)?;
     }
 
     if old_channel != new_channel {
@@ -785,7 +899,21 @@ pub fn handle_msg_recv_packet<C: Client>(
         return Err(VerifyError::WrongIBCHandlerAddress);
     }
 
-    commitment.write_no_commitment()?;
+    // Receipts of unordered channels are proven absent to time out packets.
+    // Ordered channels don't need them, timeouts are checked with the next
+    // receive sequence instead.
+    if new_channel.order == Ordering::Unordered {
+        commitment.write_commitments([(
+            packet_receipt_path(
+                &ibc_packet.packet.destination_port_id,
+                &ibc_packet.packet.destination_channel_id,
+                ibc_packet.packet.sequence,
+            ),
+            [1],
+        )])?;
+    } else {
+        commitment.write_no_commitment()?;
+    }
 
     client.verify_membership(
         msg.proof_height,
@@ -796,25 +924,10 @@ pub fn handle_msg_recv_packet<C: Client>(
             ibc_packet.packet.sequence,
         )
         .as_bytes(),
-        &sha256(&[
-            &ibc_packet.packet.timeout_timestamp.to_le_bytes(),
-            // Revision number
-            &0u64.to_le_bytes(),
-            &ibc_packet.packet.timeout_height.to_le_bytes(),
-            &sha256(&[&ibc_packet.packet.data]),
-        ]),
+        &DefaultCommitmentScheme::packet_commitment(&ibc_packet.packet),
     )
 }
 
-fn sha256(msgs: &[&[u8]]) -> [u8; 32] {
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    for m in msgs {
-        hasher.update(m);
-    }
-    hasher.finalize().into()
-}
-
 pub fn handle_msg_ack_packet<C: Client>(
     client: C,
     mut old_channel: IbcChannel,
@@ -828,6 +941,8 @@ pub fn handle_msg_ack_packet<C: Client>(
     mut commitment: impl WriteOrVerifyCommitments,
     msg: MsgAckPacket,
 ) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
     if old_ibc_packet.status != PacketStatus::Send {
         return Err(VerifyError::WrongPacketStatus);
     }
@@ -841,10 +956,14 @@ pub fn handle_msg_ack_packet<C: Client>(
         return Err(VerifyError::WrongPacketArgs);
     }
 
-    if old_ibc_packet.ack.is_some() || new_ibc_packet.ack.is_none() {
+    if old_ibc_packet.ack.is_some() {
         return Err(VerifyError::WrongPacketAck);
     }
-    old_ibc_packet.ack = new_ibc_packet.ack.clone();
+    let ack = new_ibc_packet
+        .ack
+        .as_deref()
+        .ok_or(VerifyError::WrongPacketAck)?;
+    old_ibc_packet.ack = Some(ack.to_vec());
 
     if old_ibc_packet != new_ibc_packet {
         return Err(VerifyError::WrongPacketContent);
@@ -854,18 +973,32 @@ pub fn handle_msg_ack_packet<C: Client>(
         if new_ibc_packet.packet.sequence != old_channel.sequence.next_sequence_acks {
             return Err(VerifyError::WrongPacketSequence);
         }
-        old_channel.sequence.next_sequence_acks += 1;
+        old_channel.sequence.next_sequence_acks = old_channel
+            .sequence
+            .next_sequence_acks
+            .checked_add(1)
+            .ok_or(VerifyError::WrongPacketSequence)?;
     }
 
     if old_channel != new_channel {
         return Err(VerifyError::WrongChannel);
     }
 
+    if new_channel.state != State::Open {
+        return Err(VerifyError::WrongChannelState);
+    }
+
     if new_channel_args.ibc_handler_address != new_packet_args.ibc_handler_address {
         return Err(VerifyError::WrongIBCHandlerAddress);
     }
 
-    commitment.write_no_commitment()?;
+    // The packet is done, so remove its commitment to stop proofs of it from
+    // being replayed.
+    commitment.delete_commitments([packet_commitment_path(
+        &new_ibc_packet.packet.source_port_id,
+        &new_ibc_packet.packet.source_channel_id,
+        new_ibc_packet.packet.sequence,
+    )])?;
 
     client.verify_membership(
         msg.proof_height,
@@ -876,7 +1009,86 @@ pub fn handle_msg_ack_packet<C: Client>(
             new_ibc_packet.packet.sequence,
         )
         .as_bytes(),
-        &sha256(&[&new_ibc_packet.ack.unwrap()]),
+        &DefaultCommitmentScheme::ack_commitment(ack),
+    )
+}
+
+/// Time out a packet that the destination chain hasn't received, at a proof
+/// height not before the timeout height of the packet. The packet cell is
+/// consumed and its commitment deleted.
+///
+/// Only unordered channels are supported. An ordered channel is closed on
+/// timeout, and `msg.next_sequence_recv` is not checked.
+pub fn handle_msg_timeout_packet<C: Client>(
+    client: C,
+    old_channel: IbcChannel,
+    old_channel_args: ChannelArgs,
+    new_channel: IbcChannel,
+    new_channel_args: ChannelArgs,
+    ibc_packet: IbcPacket,
+    packet_args: PacketArgs,
+    mut commitment: impl WriteOrVerifyCommitments,
+    msg: MsgTimeoutPacket,
+) -> Result<(), VerifyError> {
+    check_client(&client, msg.proof_height)?;
+
+    if ibc_packet.status != PacketStatus::Send {
+        return Err(VerifyError::WrongPacketStatus);
+    }
+
+    if ibc_packet.packet != msg.packet {
+        return Err(VerifyError::WrongPacketContent);
+    }
+
+    if old_channel != new_channel {
+        return Err(VerifyError::WrongChannel);
+    }
+
+    if new_channel.order != Ordering::Unordered {
+        return Err(VerifyError::WrongChannel);
+    }
+
+    if new_channel.state != State::Open {
+        return Err(VerifyError::WrongChannelState);
+    }
+
+    if old_channel_args != new_channel_args {
+        return Err(VerifyError::WrongChannelArgs);
+    }
+
+    packet_args.is_channel(&new_channel_args)?;
+
+    if new_channel_args.port_id_str() != ibc_packet.packet.source_port_id
+        || packet_args.sequence != ibc_packet.packet.sequence
+        || new_channel_args.channel_id_str() != ibc_packet.packet.source_channel_id
+    {
+        return Err(VerifyError::WrongPacketArgs);
+    }
+
+    if new_channel_args.ibc_handler_address != packet_args.ibc_handler_address {
+        return Err(VerifyError::WrongIBCHandlerAddress);
+    }
+
+    let timeout_height = ibc_packet.packet.timeout_height;
+    if timeout_height.is_zero() || msg.proof_height < timeout_height {
+        return Err(VerifyError::PacketNotTimedOut);
+    }
+
+    commitment.delete_commitments([packet_commitment_path(
+        &ibc_packet.packet.source_port_id,
+        &ibc_packet.packet.source_channel_id,
+        ibc_packet.packet.sequence,
+    )])?;
+
+    client.verify_non_membership(
+        msg.proof_height,
+        &msg.proof_unreceived,
+        packet_receipt_path(
+            &ibc_packet.packet.destination_port_id,
+            &ibc_packet.packet.destination_channel_id,
+            ibc_packet.packet.sequence,
+        )
+        .as_bytes(),
     )
 }
 
@@ -900,7 +1112,7 @@ pub fn handle_msg_write_ack_packet(
     }
 
     if old_ibc_packet.status != PacketStatus::Recv
-        && new_ibc_packet.status != PacketStatus::WriteAck
+        || new_ibc_packet.status != PacketStatus::WriteAck
     {
         return Err(VerifyError::WrongPacketStatus);
     }
@@ -913,9 +1125,13 @@ pub fn handle_msg_write_ack_packet(
         return Err(VerifyError::WrongPacketContent);
     }
 
-    if old_ibc_packet.ack.is_some() || new_ibc_packet.ack.is_none() {
+    if old_ibc_packet.ack.is_some() {
         return Err(VerifyError::WrongPacketAck);
     }
+    let ack = new_ibc_packet
+        .ack
+        .as_deref()
+        .ok_or(VerifyError::WrongPacketAck)?;
 
     if new_channel_args.ibc_handler_address != new_packet_args.ibc_handler_address {
         return Err(VerifyError::WrongIBCHandlerAddress);
@@ -927,7 +1143,7 @@ pub fn handle_msg_write_ack_packet(
             &new_ibc_packet.packet.destination_channel_id,
             new_ibc_packet.packet.sequence,
         ),
-        sha256(&[new_ibc_packet.ack.as_deref().unwrap()]),
+        DefaultCommitmentScheme::ack_commitment(ack),
     )])?;
 
     Ok(())
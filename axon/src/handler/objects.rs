@@ -1,15 +1,53 @@
 use alloc::{string::String, vec::Vec};
 
+use axon_tools::keccak_256;
 use rlp_derive::RlpDecodable;
 use rlp_derive::RlpEncodable;
 
-use crate::connection_id;
+use core::str::FromStr;
+
+use crate::host::{ChannelId, ClientId, ConnectionId};
 use crate::object::{ChannelCounterparty, ConnectionEnd, Ordering, Packet, State, VerifyError};
 use crate::proto;
 use crate::proto::client::Height;
 use crate::ChannelArgs;
 
+/// Objects stored in IBC cells. The cell data is the digest of the object,
+/// and the RLP encoding of the object is in the witness.
+pub trait CellObject: rlp::Encodable + rlp::Decodable + Sized {
+    /// Keccak-256 of the RLP encoding.
+    fn digest(&self) -> [u8; 32] {
+        keccak_256(&rlp::encode(self))
+    }
+
+    /// Check that the cell data is the digest of the object in the current
+    /// encoding. Cells written with a legacy encoding, e.g. packets with a
+    /// plain `u64` timeout height, don't match after decoding. Check those
+    /// with `from_witness`, which hashes the witness bytes as they are.
+    fn verify_against_cell_data(&self, cell_data: &[u8]) -> Result<(), VerifyError> {
+        if cell_data == self.digest().as_slice() {
+            Ok(())
+        } else {
+            Err(VerifyError::WrongCellData)
+        }
+    }
+
+    /// Decode the object from witness bytes, after checking that the cell
+    /// data is the digest of these bytes.
+    fn from_witness(witness: &[u8], cell_data: &[u8]) -> Result<Self, VerifyError> {
+        if cell_data != keccak_256(witness).as_slice() {
+            return Err(VerifyError::WrongCellData);
+        }
+        rlp::decode(witness).map_err(|_| VerifyError::SerdeError)
+    }
+}
+
+impl CellObject for IbcConnections {}
+impl CellObject for IbcChannel {}
+impl CellObject for IbcPacket {}
+
 #[derive(Debug, Default, Clone, RlpDecodable, RlpEncodable, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IbcConnections {
     pub next_channel_number: u64,
     pub connections: Vec<ConnectionEnd>,
@@ -17,27 +55,25 @@ pub struct IbcConnections {
 
 impl IbcConnections {
     pub fn get_by_id(&self, client_id: &str, id: &str) -> Option<&ConnectionEnd> {
-        let idx = extract_connection_index(id).ok()?;
-        let expected_id = connection_id(client_id, idx);
-        if id != expected_id {
-            return None;
-        }
+        let client_id = ClientId::from_str(client_id).ok()?;
+        let idx = ConnectionId::from_str(id).ok()?.index_of(&client_id)?;
         self.connections.get(idx)
     }
-}
 
-fn extract_connection_index(connection_id: &str) -> Result<usize, VerifyError> {
-    let index_str = connection_id
-        .split('-')
-        .last()
-        .ok_or(VerifyError::WrongConnectionId)?;
-    let index = index_str
-        .parse()
-        .map_err(|_| VerifyError::WrongConnectionId)?;
-    Ok(index)
+    pub fn to_identified_protos(
+        &self,
+        client_id: &str,
+    ) -> Vec<proto::connection::IdentifiedConnection> {
+        self.connections
+            .iter()
+            .enumerate()
+            .map(|(idx, c)| c.clone().to_identified_proto(client_id.into(), idx))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, RlpDecodable, RlpEncodable, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IbcChannel {
     pub number: u64,
     pub port_id: String,
@@ -82,15 +118,63 @@ impl From<IbcChannel> for proto::channel::Channel {
     }
 }
 
+impl From<IbcChannel> for proto::channel::IdentifiedChannel {
+    fn from(value: IbcChannel) -> Self {
+        let port_id = value.port_id.clone();
+        let channel_id = ChannelId::new(value.number).into();
+        let channel = proto::channel::Channel::from(value);
+        Self {
+            state: channel.state,
+            ordering: channel.ordering,
+            counterparty: channel.counterparty,
+            connection_hops: channel.connection_hops,
+            version: channel.version,
+            port_id,
+            channel_id,
+        }
+    }
+}
+
+/// Sequences and the counterparty connection id are not part of the proto
+/// type. They are left as default and should be filled in from the channel
+/// cell and the connection.
+impl TryFrom<proto::channel::IdentifiedChannel> for IbcChannel {
+    type Error = VerifyError;
+
+    fn try_from(value: proto::channel::IdentifiedChannel) -> Result<Self, VerifyError> {
+        let number = ChannelId::from_str(&value.channel_id)?
+            .number()
+            .ok_or(VerifyError::WrongChannel)?;
+        let counterparty = value.counterparty.ok_or(VerifyError::WrongChannel)?;
+        Ok(Self {
+            number,
+            port_id: value.port_id,
+            state: State::from_proto_channel_state(value.state)?,
+            order: Ordering::from_proto(value.ordering)?,
+            sequence: Sequence::default(),
+            counterparty: ChannelCounterparty {
+                port_id: counterparty.port_id,
+                channel_id: counterparty.channel_id,
+                connection_id: String::new(),
+            },
+            connection_hops: value.connection_hops,
+            version: value.version,
+        })
+    }
+}
+
 #[derive(RlpEncodable, RlpDecodable, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IbcPacket {
     pub packet: Packet,
     pub status: PacketStatus,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::option"))]
     pub ack: Option<Vec<u8>>,
 }
 
 impl_enum_rlp! {
     #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(u8)]
     pub enum PacketStatus {
         Send = 1,
@@ -102,6 +186,7 @@ impl_enum_rlp! {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sequence {
     pub next_sequence_sends: u64,
     pub next_sequence_recvs: u64,
@@ -133,7 +218,40 @@ impl Sequence {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClientStatus {
+    #[default]
+    Active,
+    /// Frozen after misbehaviour of the counterparty chain.
+    Frozen,
+    /// The latest trusted state is past the trusting period.
+    Expired,
+}
+
 pub trait Client {
+    /// Whether proofs can be verified by this client. Handlers reject any
+    /// proof unless it's `Active`.
+    fn status(&self) -> ClientStatus {
+        ClientStatus::Active
+    }
+
+    /// Latest height of the counterparty chain known to this client, if it
+    /// tracks one.
+    fn latest_height(&self) -> Option<Height> {
+        None
+    }
+
+    /// Check that proofs at `height` can be verified by this client: the
+    /// height is on the revision of the latest height and not after it.
+    fn validate_proof_height(&self, height: Height) -> Result<(), VerifyError> {
+        match self.latest_height() {
+            Some(latest) if height.revision_number != latest.revision_number || height > latest => {
+                Err(VerifyError::InvalidProofHeight)
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn verify_membership(
         &self,
         height: Height,
@@ -145,11 +263,119 @@ pub trait Client {
         path: &[u8],
         value: &[u8],
     ) -> Result<(), VerifyError>;
+
+    /// Verify that there's no commitment at `path`.
+    fn verify_non_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError>;
+
+    /// Verify several (path, value) pairs proven at the same height.
+    ///
+    /// The default implementation treats `proof` as an RLP list of proofs,
+    /// one for each pair, and verifies them one by one.
+    fn verify_membership_batch(
+        &self,
+        height: Height,
+        proof: &[u8],
+        kvs: &[(&[u8], &[u8])],
+    ) -> Result<(), VerifyError> {
+        let proofs: Vec<Vec<u8>> = rlp::Rlp::new(proof)
+            .as_list()
+            .map_err(|_| VerifyError::SerdeError)?;
+        if proofs.len() != kvs.len() {
+            return Err(VerifyError::SerdeError);
+        }
+        for ((path, value), proof) in kvs.iter().zip(proofs) {
+            self.verify_membership(height, &proof, path, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks by the host chain of the counterparty's client of the host chain,
+/// done in the connection handshake.
+pub trait ValidateSelfClient {
+    /// Check that `client_state` is a client of this chain with the right
+    /// chain parameters.
+    fn validate_self_client(&self, client_state: &[u8]) -> Result<(), VerifyError>;
+
+    /// Check that `consensus_state` is the consensus state of this chain at
+    /// `height`.
+    fn validate_self_consensus_state(
+        &self,
+        height: Height,
+        consensus_state: &[u8],
+    ) -> Result<(), VerifyError>;
+}
+
+impl<T> ValidateSelfClient for &T
+where
+    T: ValidateSelfClient,
+{
+    fn validate_self_client(&self, client_state: &[u8]) -> Result<(), VerifyError> {
+        T::validate_self_client(self, client_state)
+    }
+
+    fn validate_self_consensus_state(
+        &self,
+        height: Height,
+        consensus_state: &[u8],
+    ) -> Result<(), VerifyError> {
+        T::validate_self_consensus_state(self, height, consensus_state)
+    }
+}
+
+impl<T> Client for &T
+where
+    T: Client,
+{
+    fn status(&self) -> ClientStatus {
+        T::status(self)
+    }
+
+    fn latest_height(&self) -> Option<Height> {
+        T::latest_height(self)
+    }
+
+    fn validate_proof_height(&self, height: Height) -> Result<(), VerifyError> {
+        T::validate_proof_height(self, height)
+    }
+
+    fn verify_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+        value: &[u8],
+    ) -> Result<(), VerifyError> {
+        T::verify_membership(self, height, proof, path, value)
+    }
+
+    fn verify_non_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError> {
+        T::verify_non_membership(self, height, proof, path)
+    }
+
+    fn verify_membership_batch(
+        &self,
+        height: Height,
+        proof: &[u8],
+        kvs: &[(&[u8], &[u8])],
+    ) -> Result<(), VerifyError> {
+        T::verify_membership_batch(self, height, proof, kvs)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Sequence;
+    use super::*;
 
     #[test]
     fn test_unorder_receive() {
@@ -161,4 +387,188 @@ mod tests {
         assert!(s.unorder_receive(3).is_err());
         assert_eq!(s.received_sequences, [1, 2, 3, 5]);
     }
+
+    #[test]
+    fn test_identified_channel_conversion() {
+        let channel = IbcChannel {
+            number: 3,
+            state: State::Open,
+            order: Ordering::Ordered,
+            counterparty: ChannelCounterparty {
+                port_id: "transfer".into(),
+                channel_id: "channel-7".into(),
+                connection_id: String::new(),
+            },
+            connection_hops: vec!["connection-0".into()],
+            version: "ics20-1".into(),
+            ..Default::default()
+        };
+        let identified = proto::channel::IdentifiedChannel::from(channel.clone());
+        assert_eq!(identified.channel_id, "channel-3");
+        assert_eq!(identified.port_id, channel.port_id);
+        assert_eq!(IbcChannel::try_from(identified.clone()).unwrap(), channel);
+
+        let mut wrong = identified.clone();
+        wrong.channel_id = "channel-x".into();
+        assert!(matches!(
+            IbcChannel::try_from(wrong),
+            Err(VerifyError::WrongChannel)
+        ));
+        let mut wrong = identified.clone();
+        wrong.state = 9;
+        assert!(matches!(
+            IbcChannel::try_from(wrong),
+            Err(VerifyError::WrongChannelState)
+        ));
+        let mut wrong = identified;
+        wrong.counterparty = None;
+        assert!(IbcChannel::try_from(wrong).is_err());
+    }
+
+    #[test]
+    fn test_identified_connections() {
+        let connections = IbcConnections {
+            next_channel_number: 0,
+            connections: vec![ConnectionEnd::default(), ConnectionEnd::default()],
+        };
+        let client_id = "0123456789";
+        let identified = connections.to_identified_protos(client_id);
+        assert_eq!(identified.len(), 2);
+        assert_eq!(identified[1].id, "012345-connection-1");
+        assert_eq!(identified[1].client_id, client_id);
+        for (i, c) in identified.into_iter().enumerate() {
+            let end = ConnectionEnd::try_from(c).unwrap();
+            assert_eq!(end, connections.connections[i]);
+        }
+    }
+
+    fn connections_vector() -> IbcConnections {
+        IbcConnections {
+            next_channel_number: 1,
+            connections: vec![ConnectionEnd {
+                state: State::Open,
+                counterparty: crate::object::ConnectionCounterparty {
+                    client_id: "client".into(),
+                    connection_id: "connection-0".into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_digest_vectors() {
+        let connections = connections_vector();
+        assert_eq!(
+            hex::encode(connections.digest()),
+            "56ca97e81f724940b29693742b0cd81929e4224dc7664976dfc2505b42624e66"
+        );
+        assert_eq!(
+            hex::encode(IbcConnections::default().digest()),
+            "696b8ff95244b0ea5678813456c40e490bc012bce437465701639d4d2a6f4dac"
+        );
+
+        let channel = IbcChannel {
+            number: 0,
+            port_id: "transfer".into(),
+            state: State::Open,
+            order: Ordering::Ordered,
+            sequence: Sequence::default(),
+            counterparty: ChannelCounterparty {
+                port_id: "transfer".into(),
+                channel_id: "channel-1".into(),
+                connection_id: "connection-0".into(),
+            },
+            connection_hops: vec!["connection-0".into()],
+            version: "ics20-1".into(),
+        };
+        assert_eq!(
+            hex::encode(channel.digest()),
+            "f08d7fc338a158a35be7d898d9974824b6ba22f05ec54349afd1f293d29e0ac5"
+        );
+
+        let packet = IbcPacket {
+            packet: Packet {
+                sequence: 1,
+                source_port_id: "transfer".into(),
+                source_channel_id: "channel-0".into(),
+                destination_port_id: "transfer".into(),
+                destination_channel_id: "channel-1".into(),
+                data: vec![1, 2, 3],
+                timeout_height: Default::default(),
+                timeout_timestamp: 0,
+            },
+            status: PacketStatus::Send,
+            ack: None,
+        };
+        assert_eq!(
+            hex::encode(packet.digest()),
+            "e81a3e90bba99ed65d94bea549c882ae64100e3749d34ce839b0ac1a32ec3e01"
+        );
+    }
+
+    #[test]
+    fn test_verify_against_cell_data() {
+        let connections = connections_vector();
+        let cell_data = connections.digest();
+        connections.verify_against_cell_data(&cell_data).unwrap();
+        assert!(matches!(
+            IbcConnections::default().verify_against_cell_data(&cell_data),
+            Err(VerifyError::WrongCellData)
+        ));
+        assert!(connections
+            .verify_against_cell_data(&cell_data[..31])
+            .is_err());
+
+        let witness = rlp::encode(&connections);
+        assert_eq!(
+            IbcConnections::from_witness(&witness, &cell_data).unwrap(),
+            connections
+        );
+        assert!(matches!(
+            IbcConnections::from_witness(&witness[1..], &cell_data),
+            Err(VerifyError::WrongCellData)
+        ));
+        // Digest matches, but the witness isn't an object.
+        assert!(matches!(
+            IbcConnections::from_witness(b"x", &keccak_256(b"x")),
+            Err(VerifyError::SerdeError)
+        ));
+    }
+
+    #[test]
+    fn test_legacy_packet_cell() {
+        let packet = Packet {
+            sequence: 1,
+            data: vec![1, 2, 3],
+            timeout_height: Height::new(0, 30),
+            ..Default::default()
+        };
+        // A packet cell written before timeout heights had revision numbers.
+        let mut legacy_packet = rlp::RlpStream::new_list(8);
+        legacy_packet
+            .append(&packet.sequence)
+            .append(&packet.source_port_id)
+            .append(&packet.source_channel_id)
+            .append(&packet.destination_port_id)
+            .append(&packet.destination_channel_id)
+            .append(&packet.data)
+            .append(&30u64)
+            .append(&packet.timeout_timestamp);
+        let mut s = rlp::RlpStream::new_list(3);
+        s.append_raw(&legacy_packet.out(), 1)
+            .append(&PacketStatus::Send)
+            .append(&None::<Vec<u8>>);
+        let witness = s.out();
+        let cell_data = keccak_256(&witness);
+
+        let ibc_packet = IbcPacket::from_witness(&witness, &cell_data).unwrap();
+        assert_eq!(ibc_packet.packet, packet);
+        // Re-encoded with a full timeout height, so the digest differs.
+        assert!(matches!(
+            ibc_packet.verify_against_cell_data(&cell_data),
+            Err(VerifyError::WrongCellData)
+        ));
+    }
 }
@@ -1,5 +1,6 @@
 use alloc::string::String;
 
+use crate::axon_client::AxonClient;
 use crate::handler::*;
 use crate::message::CommitmentKV;
 use crate::object::ChannelCounterparty;
@@ -9,9 +10,20 @@ use crate::object::Packet;
 use crate::proto::client::Height;
 
 #[derive(Debug, Default)]
-pub struct TestClient {}
+pub struct TestClient {
+    status: ClientStatus,
+    latest_height: Option<Height>,
+}
 
 impl Client for TestClient {
+    fn status(&self) -> ClientStatus {
+        self.status
+    }
+
+    fn latest_height(&self) -> Option<Height> {
+        self.latest_height
+    }
+
     fn verify_membership(
         &self,
         _height: Height,
@@ -21,12 +33,43 @@ impl Client for TestClient {
     ) -> Result<(), VerifyError> {
         Ok(())
     }
+
+    fn verify_non_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        _path: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+}
+
+/// Accepts any client and consensus state of the host chain.
+#[derive(Debug, Default)]
+pub struct TestSelfClient;
+
+impl ValidateSelfClient for TestSelfClient {
+    fn validate_self_client(&self, _client_state: &[u8]) -> Result<(), VerifyError> {
+        Ok(())
+    }
+
+    fn validate_self_consensus_state(
+        &self,
+        _height: Height,
+        _consensus_state: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
 }
 
 #[test]
 fn test_handle_msg_connection_open_init() {
     let new_connection_end = ConnectionEnd {
         state: State::Init,
+        counterparty: ConnectionCounterparty {
+            client_id: "07-tendermint-0".into(),
+            ..Default::default()
+        },
         ..Default::default()
     };
 
@@ -55,8 +98,8 @@ fn test_handle_msg_connection_open_try() {
     let new_connection_end = ConnectionEnd {
         state: State::OpenTry,
         counterparty: ConnectionCounterparty {
-            client_id: String::from("dummy"),
-            connection_id: "dummy".into(),
+            client_id: String::from("07-tendermint-0"),
+            connection_id: "connection-0".into(),
             commitment_prefix: COMMITMENT_PREFIX.to_vec(),
         },
         ..Default::default()
@@ -74,12 +117,14 @@ fn test_handle_msg_connection_open_try() {
             revision_number: 0,
         },
         proof_init: vec![],
+        ..Default::default()
     };
     let old_args = ConnectionArgs::default();
     let new_args = ConnectionArgs::default();
 
     handle_msg_connection_open_try(
         client,
+        TestSelfClient,
         old_connections,
         old_args,
         new_connections,
@@ -94,66 +139,92 @@ fn test_handle_msg_connection_open_try() {
 fn test_handle_msg_connection_open_ack() {
     let client = TestClient::default();
 
-    let msg = MsgConnectionOpenAck {
+    let msg = || MsgConnectionOpenAck {
         conn_id_on_a: 1,
         proof_height: Height {
             revision_number: 0,
             revision_height: 0,
         },
         proof_try: vec![],
-    };
-
-    let old_connection_end = ConnectionEnd {
-        state: State::Init,
         ..Default::default()
     };
 
-    let new_connection_end = ConnectionEnd {
-        state: State::Open,
-        counterparty: ConnectionCounterparty {
-            connection_id: "connection".to_string(),
+    let connections = |client_id: &str| {
+        let old_connection_end = ConnectionEnd {
+            state: State::Init,
+            counterparty: ConnectionCounterparty {
+                client_id: client_id.into(),
+                ..Default::default()
+            },
             ..Default::default()
-        },
-        ..Default::default()
-    };
+        };
 
-    let old_connections = IbcConnections {
-        connections: vec![
-            ConnectionEnd::default(),
-            old_connection_end,
-            ConnectionEnd::default(),
-        ],
-        ..Default::default()
-    };
-
-    let new_connections = IbcConnections {
-        connections: vec![
-            ConnectionEnd::default(),
-            new_connection_end,
-            ConnectionEnd::default(),
-        ],
-        ..Default::default()
+        let new_connection_end = ConnectionEnd {
+            state: State::Open,
+            counterparty: ConnectionCounterparty {
+                client_id: client_id.into(),
+                connection_id: "connection".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let old_connections = IbcConnections {
+            connections: vec![
+                ConnectionEnd::default(),
+                old_connection_end,
+                ConnectionEnd::default(),
+            ],
+            ..Default::default()
+        };
+
+        let new_connections = IbcConnections {
+            connections: vec![
+                ConnectionEnd::default(),
+                new_connection_end,
+                ConnectionEnd::default(),
+            ],
+            ..Default::default()
+        };
+        (old_connections, new_connections)
     };
 
     let old_args = ConnectionArgs::default();
     let new_args = ConnectionArgs::default();
+    let (old_connections, new_connections) = connections("07-tendermint-0");
     handle_msg_connection_open_ack(
-        client,
+        &client,
+        TestSelfClient,
         old_connections,
         old_args,
         new_connections,
         new_args,
         &mut Vec::new(),
-        msg,
+        msg(),
     )
     .unwrap();
+
+    let (old_connections, new_connections) = connections("client");
+    assert!(matches!(
+        handle_msg_connection_open_ack(
+            &client,
+            TestSelfClient,
+            old_connections,
+            old_args,
+            new_connections,
+            new_args,
+            &mut Vec::new(),
+            msg(),
+        ),
+        Err(VerifyError::WrongClient)
+    ));
 }
 
 #[test]
 fn test_handle_msg_connection_open_confirm() {
     let client = TestClient::default();
 
-    let msg = MsgConnectionOpenConfirm {
+    let msg = || MsgConnectionOpenConfirm {
         conn_id_on_b: 1,
         proof_height: Height {
             revision_number: 0,
@@ -162,54 +233,72 @@ fn test_handle_msg_connection_open_confirm() {
         proof_ack: vec![],
     };
 
-    let old_connection_end = ConnectionEnd {
-        state: State::OpenTry,
-        counterparty: ConnectionCounterparty {
-            connection_id: "connection-1".into(),
+    let connections = |connection_id: &str| {
+        let old_connection_end = ConnectionEnd {
+            state: State::OpenTry,
+            counterparty: ConnectionCounterparty {
+                connection_id: connection_id.into(),
+                ..Default::default()
+            },
             ..Default::default()
-        },
-        ..Default::default()
-    };
+        };
 
-    let new_connection_end = ConnectionEnd {
-        state: State::Open,
-        counterparty: ConnectionCounterparty {
-            connection_id: "connection-1".into(),
+        let new_connection_end = ConnectionEnd {
+            state: State::Open,
+            counterparty: ConnectionCounterparty {
+                connection_id: connection_id.into(),
+                ..Default::default()
+            },
             ..Default::default()
-        },
-        ..Default::default()
-    };
-
-    let old_connections = IbcConnections {
-        connections: vec![
-            ConnectionEnd::default(),
-            old_connection_end,
-            ConnectionEnd::default(),
-        ],
-        ..Default::default()
-    };
-
-    let new_connections = IbcConnections {
-        connections: vec![
-            ConnectionEnd::default(),
-            new_connection_end,
-            ConnectionEnd::default(),
-        ],
-        ..Default::default()
+        };
+
+        let old_connections = IbcConnections {
+            connections: vec![
+                ConnectionEnd::default(),
+                old_connection_end,
+                ConnectionEnd::default(),
+            ],
+            ..Default::default()
+        };
+
+        let new_connections = IbcConnections {
+            connections: vec![
+                ConnectionEnd::default(),
+                new_connection_end,
+                ConnectionEnd::default(),
+            ],
+            ..Default::default()
+        };
+        (old_connections, new_connections)
     };
 
     let old_args = ConnectionArgs::default();
     let new_args = ConnectionArgs::default();
+    let (old_connections, new_connections) = connections("connection-1");
     handle_msg_connection_open_confirm(
-        client,
+        &client,
         old_connections,
         old_args,
         new_connections,
         new_args,
         &mut Vec::new(),
-        msg,
+        msg(),
     )
     .unwrap();
+
+    let (old_connections, new_connections) = connections("connection/1");
+    assert!(matches!(
+        handle_msg_connection_open_confirm(
+            &client,
+            old_connections,
+            old_args,
+            new_connections,
+            new_args,
+            &mut Vec::new(),
+            msg(),
+        ),
+        Err(VerifyError::WrongConnectionId)
+    ));
 }
 
 #[test]
@@ -234,6 +323,10 @@ fn test_handle_msg_channel_open_init() {
     let channel = IbcChannel {
         state: State::Init,
         connection_hops: vec![connection_id(&connection_args.client_id(), 0)],
+        counterparty: ChannelCounterparty {
+            port_id: "transfer".into(),
+            ..Default::default()
+        },
         ..Default::default()
     };
 
@@ -274,6 +367,11 @@ fn test_handle_msg_channel_open_try_success() {
     let channel = IbcChannel {
         state: State::OpenTry,
         connection_hops: vec![connection_id(&connection_args.client_id(), 0)],
+        counterparty: ChannelCounterparty {
+            port_id: "transfer".into(),
+            channel_id: "channel-0".into(),
+            ..Default::default()
+        },
         ..Default::default()
     };
 
@@ -681,6 +779,87 @@ fn test_msg_recv_packet_success() {
     .unwrap();
 }
 
+#[test]
+fn test_msg_recv_packet_inactive_client() {
+    let channel = IbcChannel {
+        state: State::Open,
+        ..Default::default()
+    };
+    let mut new_channel = channel.clone();
+    new_channel.sequence.next_sequence_recvs += 1;
+    let ibc_packet = IbcPacket {
+        packet: Packet {
+            sequence: 1,
+            ..Packet::default()
+        },
+        status: PacketStatus::Recv,
+        ack: None,
+    };
+    let packet_args = PacketArgs {
+        sequence: 1,
+        ..Default::default()
+    };
+
+    fn recv(
+        client: impl Client,
+        channel: &IbcChannel,
+        new_channel: &IbcChannel,
+        ibc_packet: &IbcPacket,
+        packet_args: PacketArgs,
+    ) -> Result<(), VerifyError> {
+        handle_msg_recv_packet(
+            client,
+            channel.clone(),
+            ChannelArgs::default(),
+            new_channel.clone(),
+            ChannelArgs::default(),
+            None,
+            ibc_packet.clone(),
+            packet_args,
+            &mut Vec::new(),
+            MsgRecvPacket {
+                proof_height: Height::default(),
+                proof_commitment: vec![],
+            },
+        )
+    }
+    for (status, expected) in [
+        (ClientStatus::Frozen, VerifyError::ClientFrozen),
+        (ClientStatus::Expired, VerifyError::ClientExpired),
+    ] {
+        let client = TestClient {
+            status,
+            ..Default::default()
+        };
+        let r = recv(client, &channel, &new_channel, &ibc_packet, packet_args);
+        assert_eq!(r.map_err(i8::from), Err(i8::from(expected)));
+        let client = AxonClient::default().with_status(status);
+        let r = recv(client, &channel, &new_channel, &ibc_packet, packet_args);
+        assert_eq!(r.map_err(i8::from), Err(i8::from(expected)));
+    }
+
+    // Proof height after the latest height of the client.
+    let r = handle_msg_recv_packet(
+        TestClient {
+            latest_height: Some(Height::new(0, 9)),
+            ..Default::default()
+        },
+        channel,
+        ChannelArgs::default(),
+        new_channel,
+        ChannelArgs::default(),
+        None,
+        ibc_packet,
+        packet_args,
+        &mut Vec::new(),
+        MsgRecvPacket {
+            proof_height: Height::new(0, 10),
+            proof_commitment: vec![],
+        },
+    );
+    assert!(matches!(r, Err(VerifyError::InvalidProofHeight)));
+}
+
 #[test]
 fn test_msg_ack_outbox_packet_success() {
     let old_channel = IbcChannel {
@@ -793,6 +972,188 @@ fn test_msg_ack_outbox_packet_differenct_packet() {
     }
 }
 
+#[test]
+fn test_connection_index_out_of_range() {
+    let connections = IbcConnections {
+        connections: vec![ConnectionEnd::default()],
+        ..Default::default()
+    };
+    let proof_height = Height::default();
+
+    let r = handle_msg_connection_open_ack(
+        TestClient::default(),
+        TestSelfClient,
+        connections.clone(),
+        ConnectionArgs::default(),
+        connections.clone(),
+        ConnectionArgs::default(),
+        &mut Vec::new(),
+        MsgConnectionOpenAck {
+            conn_id_on_a: 1,
+            proof_height,
+            proof_try: vec![],
+            ..Default::default()
+        },
+    );
+    assert!(matches!(r, Err(VerifyError::WrongConnectionId)));
+
+    let r = handle_msg_connection_open_confirm(
+        TestClient::default(),
+        connections.clone(),
+        ConnectionArgs::default(),
+        connections,
+        ConnectionArgs::default(),
+        &mut Vec::new(),
+        MsgConnectionOpenConfirm {
+            conn_id_on_b: usize::MAX,
+            proof_height,
+            proof_ack: vec![],
+        },
+    );
+    assert!(matches!(r, Err(VerifyError::WrongConnectionId)));
+}
+
+#[test]
+fn test_channel_without_connection_hops() {
+    let channel = IbcChannel {
+        state: State::Init,
+        ..Default::default()
+    };
+    let old_connections = IbcConnections::default();
+    let new_connections = IbcConnections {
+        next_channel_number: 1,
+        ..Default::default()
+    };
+    let r = handle_msg_channel_open_init(
+        old_connections,
+        ConnectionArgs::default(),
+        new_connections,
+        ConnectionArgs::default(),
+        channel,
+        ChannelArgs::default(),
+        &mut Vec::new(),
+    );
+    assert!(matches!(r, Err(VerifyError::ConnectionsWrong)));
+}
+
+#[test]
+fn test_msg_ack_packet_without_ack() {
+    let channel = IbcChannel {
+        state: State::Open,
+        order: Ordering::Unordered,
+        ..Default::default()
+    };
+    let old_ibc_packet = IbcPacket {
+        packet: Packet::default(),
+        status: PacketStatus::Send,
+        ack: None,
+    };
+    let new_ibc_packet = IbcPacket {
+        status: PacketStatus::Ack,
+        ..old_ibc_packet.clone()
+    };
+    let r = handle_msg_ack_packet(
+        TestClient::default(),
+        channel.clone(),
+        ChannelArgs::default(),
+        channel,
+        ChannelArgs::default(),
+        old_ibc_packet,
+        PacketArgs::default(),
+        new_ibc_packet,
+        PacketArgs::default(),
+        &mut Vec::new(),
+        MsgAckPacket {
+            proof_height: Height::default(),
+            proof_acked: vec![],
+        },
+    );
+    assert!(matches!(r, Err(VerifyError::WrongPacketAck)));
+}
+
+#[test]
+fn test_invalid_counterparty_identifiers() {
+    let connection_end = ConnectionEnd {
+        state: State::Open,
+        ..Default::default()
+    };
+    let connection_args = ConnectionArgs::default();
+    let old_connections = IbcConnections {
+        next_channel_number: 0,
+        connections: vec![connection_end.clone()],
+    };
+    let new_connections = IbcConnections {
+        next_channel_number: 1,
+        connections: vec![connection_end],
+    };
+    let channel = |port_id: &str, channel_id: &str| IbcChannel {
+        state: State::OpenTry,
+        connection_hops: vec![connection_id(&connection_args.client_id(), 0)],
+        counterparty: ChannelCounterparty {
+            port_id: port_id.into(),
+            channel_id: channel_id.into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let open_try = |channel| {
+        handle_msg_channel_open_try(
+            TestClient::default(),
+            old_connections.clone(),
+            connection_args,
+            new_connections.clone(),
+            connection_args,
+            channel,
+            ChannelArgs::default(),
+            &mut Vec::new(),
+            MsgChannelOpenTry {
+                proof_height: Height::default(),
+                proof_init: vec![],
+            },
+        )
+    };
+
+    open_try(channel("transfer", "channel-0")).unwrap();
+    assert!(matches!(
+        open_try(channel("transfer", "")),
+        Err(VerifyError::WrongChannel)
+    ));
+    assert!(matches!(
+        open_try(channel("trans/fer", "channel-0")),
+        Err(VerifyError::WrongPortId)
+    ));
+
+    // Connection ids of other clients don't refer to our connections.
+    let mut wrong_hop = channel("transfer", "channel-0");
+    wrong_hop.connection_hops = vec!["connection-0".into()];
+    assert!(matches!(
+        open_try(wrong_hop),
+        Err(VerifyError::WrongConnectionId)
+    ));
+
+    let new_connections = IbcConnections {
+        connections: vec![ConnectionEnd {
+            state: State::Init,
+            counterparty: ConnectionCounterparty {
+                client_id: "client".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(matches!(
+        handle_msg_connection_open_init(
+            IbcConnections::default(),
+            connection_args,
+            new_connections,
+            connection_args,
+            &mut Vec::new(),
+        ),
+        Err(VerifyError::WrongClient)
+    ));
+}
+
 #[test]
 fn test_ibc_connection_encode_and_decode() {
     let mut conn = IbcConnections::default();
@@ -824,6 +1185,109 @@ impl Client for ClientWithCommitments {
             Err(VerifyError::Mpt)
         }
     }
+
+    fn verify_non_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError> {
+        let CommitmentKV(path_hash, _) = CommitmentKV::deletion(path);
+        if self
+            .commitments
+            .iter()
+            .any(|c| c.0 == path_hash && !c.is_deletion())
+        {
+            Err(VerifyError::Mpt)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_verify_membership_batch() {
+    let mut commitments = Vec::new();
+    commitments
+        .write_commitments([("a", "1"), ("b", "2")])
+        .unwrap();
+    let client = client_with_commitments(commitments);
+
+    let proof = rlp::encode_list::<Vec<u8>, _>(&[vec![], vec![]]);
+    let kvs: [(&[u8], &[u8]); 2] = [(b"a", b"1"), (b"b", b"2")];
+    client
+        .verify_membership_batch(Height::default(), &proof, &kvs)
+        .unwrap();
+    let kvs: [(&[u8], &[u8]); 2] = [(b"a", b"1"), (b"b", b"3")];
+    client
+        .verify_membership_batch(Height::default(), &proof, &kvs)
+        .unwrap_err();
+    // Number of proofs doesn't match number of pairs.
+    client
+        .verify_membership_batch(Height::default(), &proof, &kvs[..1])
+        .unwrap_err();
+}
+
+fn self_client_commitments(client_id: &str) -> [CommitmentKV; 2] {
+    [
+        CommitmentKV::hash(client_state_path(client_id), b"client"),
+        CommitmentKV::hash(
+            consensus_state_path(client_id, Height::new(0, 1)),
+            b"consensus",
+        ),
+    ]
+}
+
+fn self_client_msg_try() -> MsgConnectionOpenTry {
+    MsgConnectionOpenTry {
+        client_state_of_b_on_a: b"client".to_vec(),
+        consensus_height_of_b_on_a: Height::new(0, 1),
+        consensus_state_of_b_on_a: b"consensus".to_vec(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_self_client_is_validated() {
+    struct RejectingSelfClient;
+
+    impl ValidateSelfClient for RejectingSelfClient {
+        fn validate_self_client(&self, _client_state: &[u8]) -> Result<(), VerifyError> {
+            Err(VerifyError::InvalidSelfClient)
+        }
+
+        fn validate_self_consensus_state(
+            &self,
+            _height: Height,
+            _consensus_state: &[u8],
+        ) -> Result<(), VerifyError> {
+            Ok(())
+        }
+    }
+
+    let new_connections = IbcConnections {
+        connections: vec![ConnectionEnd {
+            state: State::OpenTry,
+            counterparty: ConnectionCounterparty {
+                client_id: "07-tendermint-0".into(),
+                connection_id: "connection-0".into(),
+                commitment_prefix: COMMITMENT_PREFIX.to_vec(),
+            },
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let r = handle_msg_connection_open_try(
+        TestClient::default(),
+        RejectingSelfClient,
+        IbcConnections::default(),
+        ConnectionArgs::default(),
+        new_connections,
+        ConnectionArgs::default(),
+        &mut Vec::new(),
+        MsgConnectionOpenTry::default(),
+    );
+    assert!(matches!(r, Err(VerifyError::InvalidSelfClient)));
 }
 
 #[test]
@@ -856,6 +1320,9 @@ fn test_connection_commitment_ping_pong() {
     )
     .unwrap();
 
+    // A's client of B, checked by B in the try step.
+    init_commitments.extend(self_client_commitments(&a_args.client_id()));
+
     let mut try_commitments = Vec::new();
     let b_connections_after_try = IbcConnections {
         connections: vec![ConnectionEnd {
@@ -870,18 +1337,33 @@ fn test_connection_commitment_ping_pong() {
         ..Default::default()
     };
     handle_msg_connection_open_try(
-        client_with_commitments(init_commitments),
+        client_with_commitments(init_commitments.clone()),
+        TestSelfClient,
         a_connections_before_init.clone(),
         b_args,
         b_connections_after_try.clone(),
         b_args,
         &mut try_commitments,
-        MsgConnectionOpenTry {
-            proof_height: Height::default(),
-            proof_init: vec![],
-        },
+        self_client_msg_try(),
     )
     .unwrap();
+    // A doesn't have the client state that B is shown.
+    let r = handle_msg_connection_open_try(
+        client_with_commitments(init_commitments),
+        TestSelfClient,
+        a_connections_before_init.clone(),
+        b_args,
+        b_connections_after_try.clone(),
+        b_args,
+        &mut Vec::new(),
+        MsgConnectionOpenTry {
+            client_state_of_b_on_a: b"other client".to_vec(),
+            ..self_client_msg_try()
+        },
+    );
+    assert!(matches!(r, Err(VerifyError::Mpt)));
+    // B's client of A, checked by A in the ack step.
+    try_commitments.extend(self_client_commitments(&b_args.client_id()));
 
     let mut ack_commitments = Vec::new();
     let a_connection_after_ack = IbcConnections {
@@ -898,6 +1380,7 @@ fn test_connection_commitment_ping_pong() {
     };
     handle_msg_connection_open_ack(
         client_with_commitments(try_commitments),
+        TestSelfClient,
         a_connections_after_init.clone(),
         a_args,
         a_connection_after_ack,
@@ -907,6 +1390,10 @@ fn test_connection_commitment_ping_pong() {
             conn_id_on_a: 0,
             proof_height: Height::default(),
             proof_try: vec![],
+            client_state_of_a_on_b: b"client".to_vec(),
+            consensus_height_of_a_on_b: Height::new(0, 1),
+            consensus_state_of_a_on_b: b"consensus".to_vec(),
+            ..Default::default()
         },
     )
     .unwrap();
@@ -1097,7 +1584,7 @@ fn test_channel_packet_commitment_ping_pong() {
             destination_port_id: b_channel_args.port_id_str(),
             destination_channel_id: b_channel_args.channel_id_str(),
             data: vec![73; 8],
-            timeout_height: 0,
+            timeout_height: Default::default(),
             timeout_timestamp: 0,
         },
         status: PacketStatus::Send,
@@ -1134,6 +1621,7 @@ fn test_channel_packet_commitment_ping_pong() {
         port_id: b_channel_args.port_id,
         sequence: 1,
     };
+    let mut recv_commitments = Vec::new();
     handle_msg_recv_packet(
         client_with_commitments(send_commitments),
         b_channel_confirm,
@@ -1143,13 +1631,24 @@ fn test_channel_packet_commitment_ping_pong() {
         None,
         b_packet.clone(),
         b_packet_args,
-        &mut Vec::new(),
+        &mut recv_commitments,
         MsgRecvPacket {
             proof_height: Height::default(),
             proof_commitment: vec![],
         },
     )
     .unwrap();
+    assert_eq!(
+        recv_commitments,
+        [CommitmentKV::hash(
+            packet_receipt_path(
+                &b_channel_args.port_id_str(),
+                &b_channel_args.channel_id_str(),
+                1
+            ),
+            [1],
+        )]
+    );
 
     let mut b_packet_ack = b_packet.clone();
     b_packet_ack.status = PacketStatus::WriteAck;
@@ -1173,23 +1672,33 @@ fn test_channel_packet_commitment_ping_pong() {
     a_packet_acked.status = PacketStatus::Ack;
     a_packet_acked.ack = Some("ack".into());
     let a_channel_acked = a_channel_sent.clone();
+    let mut delete_commitments = Vec::new();
     handle_msg_ack_packet(
         client_with_commitments(ack_commitments),
         a_channel_sent,
         a_channel_args_open,
         a_channel_acked.clone(),
         a_channel_args_open,
-        packet,
+        packet.clone(),
         a_packet_args,
         a_packet_acked,
         a_packet_args,
-        &mut Vec::new(),
+        &mut delete_commitments,
         MsgAckPacket {
             proof_height: Height::default(),
             proof_acked: vec![],
         },
     )
     .unwrap();
+    assert_eq!(
+        delete_commitments,
+        [CommitmentKV::deletion(packet_commitment_path(
+            &packet.packet.source_port_id,
+            &packet.packet.source_channel_id,
+            1,
+        ))]
+    );
+    assert!(delete_commitments[0].is_deletion());
 
     let mut a_channel_closed = a_channel_acked.clone();
     a_channel_closed.state = State::Closed;
@@ -1219,3 +1728,87 @@ fn test_channel_packet_commitment_ping_pong() {
     )
     .unwrap();
 }
+
+#[test]
+fn test_msg_timeout_packet() {
+    let channel = IbcChannel {
+        state: State::Open,
+        order: Ordering::Unordered,
+        ..Default::default()
+    };
+    let channel_args = ChannelArgs::default();
+    let ibc_packet = IbcPacket {
+        packet: Packet {
+            sequence: 1,
+            source_port_id: channel_args.port_id_str(),
+            source_channel_id: channel_args.channel_id_str(),
+            destination_port_id: channel_args.port_id_str(),
+            destination_channel_id: "channel-1".into(),
+            timeout_height: Height::new(0, 5),
+            ..Default::default()
+        },
+        status: PacketStatus::Send,
+        ack: None,
+    };
+    let packet_args = PacketArgs {
+        sequence: 1,
+        ..Default::default()
+    };
+    let receipt = CommitmentKV::hash(
+        packet_receipt_path(&channel_args.port_id_str(), "channel-1", 1),
+        [1],
+    );
+
+    let timeout = |channel: &IbcChannel,
+                   receipts: Vec<CommitmentKV>,
+                   proof_height: Height,
+                   commitments: &mut Vec<CommitmentKV>| {
+        handle_msg_timeout_packet(
+            client_with_commitments(receipts),
+            channel.clone(),
+            channel_args,
+            channel.clone(),
+            channel_args,
+            ibc_packet.clone(),
+            packet_args,
+            commitments,
+            MsgTimeoutPacket {
+                packet: ibc_packet.packet.clone(),
+                next_sequence_recv: vec![],
+                proof_height,
+                proof_unreceived: vec![],
+            },
+        )
+    };
+
+    let mut commitments = Vec::new();
+    timeout(&channel, vec![], Height::new(0, 5), &mut commitments).unwrap();
+    assert_eq!(
+        commitments,
+        [CommitmentKV::deletion(packet_commitment_path(
+            &channel_args.port_id_str(),
+            &channel_args.channel_id_str(),
+            1,
+        ))]
+    );
+
+    // Not timed out yet.
+    assert!(matches!(
+        timeout(&channel, vec![], Height::new(0, 4), &mut Vec::new()),
+        Err(VerifyError::PacketNotTimedOut)
+    ));
+    // Received by the destination chain.
+    assert!(matches!(
+        timeout(&channel, vec![receipt], Height::new(0, 5), &mut Vec::new()),
+        Err(VerifyError::Mpt)
+    ));
+    // Ordered channels are not supported.
+    let ordered = IbcChannel {
+        order: Ordering::Ordered,
+        ..channel.clone()
+    };
+    assert!(matches!(
+        timeout(&ordered, vec![], Height::new(0, 5), &mut Vec::new()),
+        Err(VerifyError::WrongChannel)
+    ));
+}
@@ -0,0 +1,208 @@
+//! ICS-24 host identifiers.
+//!
+//! Identifiers are validated when parsed and keep the original string, so
+//! they format back to exactly what was parsed.
+
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::consts::{CHANNEL_ID_PREFIX, CONNECTION_ID_PREFIX};
+use crate::object::VerifyError;
+
+/// Whether `id` has a valid length and only contains characters allowed by
+/// ICS-24: alphanumerics and `.`, `_`, `+`, `-`, `#`, `[`, `]`, `<`, `>`.
+fn is_valid_identifier(id: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b".-_+#[]<>".contains(&b))
+}
+
+/// Parse a decimal number without sign or leading zeros, so that it formats
+/// back to the same string.
+fn parse_canonical<T: FromStr>(s: &str) -> Option<T> {
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) || !s.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    s.parse().ok()
+}
+
+macro_rules! identifier {
+    ($(#[$meta:meta])* $name:ident, $min:literal..=$max:literal, $err:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = VerifyError;
+
+            fn from_str(s: &str) -> Result<Self, VerifyError> {
+                if is_valid_identifier(s, $min, $max) {
+                    Ok(Self(s.into()))
+                } else {
+                    Err(VerifyError::$err)
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+identifier!(
+    /// Client identifier. Clients of Axon chains are identified by the hex of
+    /// the first 20 bytes of the hash of connection args.
+    ClientId,
+    9..=64,
+    WrongClient
+);
+
+identifier!(
+    /// Connection identifier. Connections on CKB are identified by
+    /// `<first 6 characters of client id>-connection-<index>`.
+    ConnectionId,
+    10..=64,
+    WrongConnectionId
+);
+
+identifier!(
+    /// Channel identifier. Channels on CKB are identified by
+    /// `channel-<number>`.
+    ChannelId,
+    8..=64,
+    WrongChannel
+);
+
+identifier!(
+    /// Port identifier. Ports on CKB are identified by the hex of the port
+    /// owner's lock hash.
+    PortId,
+    2..=128,
+    WrongPortId
+);
+
+impl ClientId {
+    pub fn from_args(args: &crate::ConnectionArgs) -> Self {
+        Self(args.client_id())
+    }
+}
+
+impl ConnectionId {
+    pub fn new(client_id: &ClientId, connection_idx: usize) -> Self {
+        Self(crate::connection_id(client_id.as_str(), connection_idx))
+    }
+
+    /// Index of a connection of `client_id`, or `None` if this isn't one of
+    /// its connection ids.
+    pub fn index_of(&self, client_id: &ClientId) -> Option<usize> {
+        let (prefix, idx) = self.0.rsplit_once(CONNECTION_ID_PREFIX)?;
+        let idx = parse_canonical(idx)?;
+        (prefix.strip_suffix('-')? == client_id.0.get(..6)?).then_some(idx)
+    }
+}
+
+impl ChannelId {
+    pub fn new(number: u64) -> Self {
+        Self(format!("{CHANNEL_ID_PREFIX}{number}"))
+    }
+
+    /// Channel number, or `None` if this isn't a channel id on CKB.
+    pub fn number(&self) -> Option<u64> {
+        parse_canonical(self.0.strip_prefix(CHANNEL_ID_PREFIX)?)
+    }
+}
+
+impl PortId {
+    pub fn from_bytes(port_id: &[u8; 32]) -> Self {
+        Self(hex::encode(port_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConnectionArgs;
+
+    #[test]
+    fn test_identifier_rules() {
+        assert!("07-tendermint-0".parse::<ClientId>().is_ok());
+        assert!("short".parse::<ClientId>().is_err());
+        assert!("connection-0".parse::<ConnectionId>().is_ok());
+        assert!("connection/0".parse::<ConnectionId>().is_err());
+        assert!("channel-0".parse::<ChannelId>().is_ok());
+        assert!("channel".parse::<ChannelId>().is_err());
+        assert!("transfer".parse::<PortId>().is_ok());
+        assert!("a".parse::<PortId>().is_err());
+        assert!("port id".parse::<PortId>().is_err());
+        assert!("x".repeat(129).parse::<PortId>().is_err());
+        assert!("[a#b]<c>+_.".parse::<PortId>().is_ok());
+    }
+
+    #[test]
+    fn test_lossless() {
+        for id in ["channel-0", "channel-007", "channel-x", "chan-nel-3"] {
+            assert_eq!(id.parse::<ChannelId>().unwrap().to_string(), id);
+        }
+        assert_eq!(ChannelId::new(12).as_str(), "channel-12");
+        assert_eq!(ChannelId::new(12).number(), Some(12));
+        assert_eq!("channel-012".parse::<ChannelId>().unwrap().number(), None);
+        assert_eq!("channel-x".parse::<ChannelId>().unwrap().number(), None);
+        assert_eq!(PortId::from_bytes(&[0xab; 32]).to_string(), "ab".repeat(32));
+    }
+
+    #[test]
+    fn test_connection_id() {
+        let client_id = ClientId::from_args(&ConnectionArgs::default());
+        let other = ClientId::from_args(&ConnectionArgs {
+            metadata_type_id: [1; 32],
+            ..Default::default()
+        });
+        let id = ConnectionId::new(&client_id, 3);
+        assert_eq!(id.as_str(), crate::connection_id(client_id.as_str(), 3));
+        assert_eq!(id.index_of(&client_id), Some(3));
+        assert_eq!(id.index_of(&other), None);
+
+        let id: ConnectionId = "connection-3".parse().unwrap();
+        assert_eq!(id.index_of(&client_id), None);
+        let id: ConnectionId = format!("{}-connection-03", &client_id.as_str()[..6])
+            .parse()
+            .unwrap();
+        assert_eq!(id.index_of(&client_id), None);
+    }
+}
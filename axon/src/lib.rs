@@ -1,5 +1,17 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![allow(clippy::result_unit_err)]
+// Handlers and the decoding and verification they call run on untrusted
+// witnesses and cell data. Malformed input must be rejected with an error
+// instead of panicking. Generated code and test utilities are exempt.
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::indexing_slicing,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic
+    )
+)]
 
 #[macro_use]
 extern crate alloc;
@@ -27,29 +39,59 @@ macro_rules! impl_enum_rlp {
             }
         }
 
-        impl rlp::Decodable for $name {
-            fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
-                let v: $type = rlp::Decodable::decode(rlp)?;
+        impl core::convert::TryFrom<$type> for $name {
+            type Error = ();
+
+            fn try_from(v: $type) -> Result<Self, ()> {
                 match v {
                     $(x if x == $name::$vname as $type => Ok($name::$vname),)*
-                    _ => Err(rlp::DecoderError::Custom(concat!("invalid value for ", stringify!($name)))),
+                    _ => Err(()),
                 }
             }
         }
+
+        impl rlp::Decodable for $name {
+            fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+                let v: $type = rlp::Decodable::decode(rlp)?;
+                $name::try_from(v).map_err(|_| {
+                    rlp::DecoderError::Custom(concat!("invalid value for ", stringify!($name)))
+                })
+            }
+        }
     }
 }
 
 pub mod axon_client;
 pub mod commitment;
 pub mod consts;
+pub mod eth_client;
 pub mod handler;
+pub mod host;
 pub mod message;
+pub mod mol;
 pub mod object;
+#[allow(
+    clippy::indexing_slicing,
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic
+)]
 pub mod proto;
+#[cfg(feature = "serde")]
+mod serde_hex;
+#[cfg(feature = "smt")]
+pub mod smt;
+#[cfg(any(test, feature = "testing"))]
+#[allow(
+    clippy::indexing_slicing,
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic
+)]
+pub mod testing;
 pub use axon_tools;
 
 use axon_tools::keccak_256;
-use consts::CHANNEL_ID_PREFIX;
 use object::VerifyError;
 
 pub type U256 = Vec<u8>;
@@ -57,16 +99,16 @@ pub type Bytes = Vec<u8>;
 
 macro_rules! try_read {
     ($buf:ident, $len:literal) => {{
-        let x: &[u8; $len] = $buf.get(..$len).ok_or(())?.try_into().unwrap();
-        $buf = &$buf[$len..];
+        let (x, rest) = $buf.split_first_chunk::<$len>().ok_or(())?;
+        $buf = rest;
         x
     }};
 }
 
 macro_rules! try_read_last {
     ($buf:ident, $len:literal) => {{
-        let x: &[u8; $len] = $buf.get(..$len).ok_or(())?.try_into().unwrap();
-        $buf = &$buf[$len..];
+        let (x, rest) = $buf.split_first_chunk::<$len>().ok_or(())?;
+        $buf = rest;
         if !$buf.is_empty() {
             return Err(());
         }
@@ -76,8 +118,11 @@ macro_rules! try_read_last {
 
 // The args of the connection cell's script
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectionArgs {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub metadata_type_id: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub ibc_handler_address: [u8; 20],
 }
 
@@ -100,8 +145,11 @@ impl ConnectionArgs {
 
 // The args of the channel cell's script
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelArgs {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub metadata_type_id: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub ibc_handler_address: [u8; 20],
     // For the sake of convenience, we use a bool here to describe
     // whether this channel is open. Relayer search the the unopen channel cell
@@ -109,6 +157,7 @@ pub struct ChannelArgs {
     pub open: bool,
     // Relayer will search the specified channel by channel id and port id
     pub channel_id: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub port_id: [u8; 32],
 }
 
@@ -131,11 +180,11 @@ impl ChannelArgs {
     }
 
     pub fn channel_id_str(&self) -> String {
-        format!("{CHANNEL_ID_PREFIX}{}", self.channel_id)
+        host::ChannelId::new(self.channel_id).into()
     }
 
     pub fn port_id_str(&self) -> String {
-        hex::encode(self.port_id)
+        host::PortId::from_bytes(&self.port_id).into()
     }
 
     pub fn get_prefix_for_searching_unopen(&self) -> Vec<u8> {
@@ -151,13 +200,10 @@ impl ChannelArgs {
         [&self.metadata_type_id[..], &self.ibc_handler_address].concat()
     }
 
+    /// Read `open` from encoded args, the same way as `from_slice`.
     pub fn is_open(data: Vec<u8>) -> Result<bool, ()> {
-        let open_byte = data.get(32 + 20 + 1).ok_or(())?;
-        if *open_byte == 1 {
-            Ok(true)
-        } else {
-            Ok(false)
-        }
+        let open_byte = data.get(32 + 20).ok_or(())?;
+        Ok(*open_byte != 0)
     }
 
     pub fn to_args(self) -> Vec<u8> {
@@ -173,9 +219,12 @@ impl ChannelArgs {
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PacketArgs {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub ibc_handler_address: [u8; 20], // distinguish different packet cells with same channel and port
     pub channel_id: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub port_id: [u8; 32], // mark as owner_lockhash
     pub sequence: u64,
 }
@@ -222,10 +271,13 @@ impl PacketArgs {
     }
 }
 
+/// Connection id of the `connection_idx`th connection of a client, prefixed
+/// by the first 6 characters of the client id. Shorter client ids are used in
+/// full.
 pub fn connection_id(client_id: &str, connection_idx: usize) -> String {
     format!(
         "{}-{}{}",
-        &client_id[..6],
+        client_id.get(..6).unwrap_or(client_id),
         consts::CONNECTION_ID_PREFIX,
         connection_idx
     )
@@ -243,6 +295,15 @@ pub trait WriteOrVerifyCommitments {
     where
         K: AsRef<[u8]>,
         V: AsRef<[u8]>;
+
+    /// Delete commitments at `paths`. A deletion is written as a commitment
+    /// with a zero value.
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>;
 }
 
 impl<T> WriteOrVerifyCommitments for &mut T
@@ -259,11 +320,56 @@ where
     {
         T::write_commitments(self, kvs)
     }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        T::delete_commitments(self, paths)
+    }
+}
+
+/// Write or verify commitments with both, e.g. the envelope commitments and
+/// an update of the commitment tree.
+impl<A, B> WriteOrVerifyCommitments for (A, B)
+where
+    A: WriteOrVerifyCommitments,
+    B: WriteOrVerifyCommitments,
+{
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let kvs: Vec<(K, V)> = kvs.into_iter().collect();
+        self.0
+            .write_commitments(kvs.iter().map(|(k, v)| (k.as_ref(), v.as_ref())))?;
+        self.1
+            .write_commitments(kvs.iter().map(|(k, v)| (k.as_ref(), v.as_ref())))
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let paths: Vec<K> = paths.into_iter().collect();
+        self.0.delete_commitments(paths.iter().map(AsRef::as_ref))?;
+        self.1.delete_commitments(paths.iter().map(AsRef::as_ref))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ChannelArgs;
+    use crate::{connection_id, ChannelArgs};
 
     #[test]
     fn channel_args_conversion() {
@@ -278,4 +384,29 @@ mod tests {
         let actual = ChannelArgs::from_slice(&slice).unwrap();
         assert_eq!(channel_args, actual);
     }
+
+    #[test]
+    fn channel_args_is_open() {
+        for open in [true, false] {
+            let args = ChannelArgs {
+                open,
+                channel_id: 3,
+                ..Default::default()
+            };
+            assert_eq!(ChannelArgs::is_open(args.to_args()), Ok(open));
+        }
+        // Any non-zero byte is open, as in `from_slice`.
+        let mut data = ChannelArgs::default().to_args();
+        data[32 + 20] = 2;
+        assert_eq!(ChannelArgs::is_open(data.clone()), Ok(true));
+        assert!(ChannelArgs::from_slice(&data).unwrap().open);
+        assert_eq!(ChannelArgs::is_open(vec![0; 52]), Err(()));
+    }
+
+    #[test]
+    fn connection_id_of_short_client_id() {
+        assert_eq!(connection_id("0123456789", 2), "012345-connection-2");
+        assert_eq!(connection_id("01", 0), "01-connection-0");
+        assert_eq!(connection_id("", 0), "-connection-0");
+    }
 }
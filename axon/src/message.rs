@@ -9,14 +9,90 @@ use super::U256;
 use crate::proto::client::Height;
 use crate::WriteOrVerifyCommitments;
 
-#[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub msg_type: MsgType,
     pub commitments: Vec<CommitmentKV>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub content: Vec<u8>,
+    /// Optional `commitments_root` of `commitments`, so that indexers can
+    /// check commitments without decoding the content.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub commitments_root: Option<H256>,
+}
+
+impl Envelope {
+    /// Set `commitments_root` to the root of `commitments`.
+    pub fn with_commitments_root(mut self) -> Result<Self, VerifyError> {
+        self.commitments_root = Some(commitments_root(&self.commitments)?);
+        Ok(self)
+    }
+
+    /// Check that `commitments_root`, if present, is the root of
+    /// `commitments`.
+    pub fn verify_commitments_root(&self) -> Result<(), VerifyError> {
+        match self.commitments_root {
+            Some(root) if root != commitments_root(&self.commitments)? => {
+                Err(VerifyError::Commitment)
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
-// Verify.
+// Envelopes without a commitments root are encoded as a 3-item list, the same
+// as before the root was added.
+impl rlp::Encodable for Envelope {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(3 + self.commitments_root.is_some() as usize);
+        s.append(&self.msg_type);
+        s.append_list(&self.commitments);
+        s.append(&self.content);
+        if let Some(root) = &self.commitments_root {
+            s.append(root);
+        }
+    }
+}
+
+impl rlp::Decodable for Envelope {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        let commitments_root = match rlp.item_count()? {
+            3 => None,
+            4 => Some(rlp.val_at(3)?),
+            _ => return Err(rlp::DecoderError::RlpIncorrectListLen),
+        };
+        Ok(Self {
+            msg_type: rlp.val_at(0)?,
+            commitments: rlp.list_at(1)?,
+            content: rlp.val_at(2)?,
+            commitments_root,
+        })
+    }
+}
+
+/// Hash of commitments sorted by path: keccak256 of the concatenated path
+/// and value hashes. Fails if a path appears more than once.
+pub fn commitments_root(kvs: &[CommitmentKV]) -> Result<H256, VerifyError> {
+    let data: Vec<u8> = sorted_commitments(kvs)?
+        .into_iter()
+        .flat_map(|kv| [kv.0.as_bytes(), kv.1.as_bytes()].concat())
+        .collect();
+    Ok(keccak_256(&data).into())
+}
+
+fn sorted_commitments(kvs: &[CommitmentKV]) -> Result<Vec<&CommitmentKV>, VerifyError> {
+    let mut sorted: Vec<&CommitmentKV> = kvs.iter().collect();
+    sorted.sort_unstable_by_key(|kv| kv.0);
+    if sorted.windows(2).any(|w| matches!(w, [a, b] if a.0 == b.0)) {
+        return Err(VerifyError::DuplicateCommitmentPath);
+    }
+    Ok(sorted)
+}
+
+// Verify. Commitments are compared as sets, so they can be in any order.
 impl WriteOrVerifyCommitments for &[CommitmentKV] {
     fn write_commitments<K, V>(
         &mut self,
@@ -28,11 +104,30 @@ impl WriteOrVerifyCommitments for &[CommitmentKV] {
     {
         let mut expected: Vec<CommitmentKV> = Vec::new();
         expected.write_commitments(kvs)?;
-        if **self == expected {
-            Ok(())
-        } else {
-            Err(VerifyError::Commitment)
-        }
+        verify_commitments(self, &expected)
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut expected: Vec<CommitmentKV> = Vec::new();
+        expected.delete_commitments(paths)?;
+        verify_commitments(self, &expected)
+    }
+}
+
+fn verify_commitments(
+    actual: &[CommitmentKV],
+    expected: &[CommitmentKV],
+) -> Result<(), VerifyError> {
+    if sorted_commitments(actual)? == sorted_commitments(expected)? {
+        Ok(())
+    } else {
+        Err(VerifyError::Commitment)
     }
 }
 
@@ -52,11 +147,23 @@ impl WriteOrVerifyCommitments for Vec<CommitmentKV> {
             .collect();
         Ok(())
     }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        *self = paths.into_iter().map(CommitmentKV::deletion).collect();
+        Ok(())
+    }
 }
 
 impl_enum_rlp!(
     #[repr(u8)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub enum MsgType {
         MsgClientCreate = 1,
         MsgClientUpdate,
@@ -86,14 +193,17 @@ impl_enum_rlp!(
 );
 
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgClientCreate {}
 
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgClientUpdate {}
 
 /// Per our convention, this message is sent to chain A.
 /// The handler will check proofs of Chain B.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgConnectionOpenInit {
     // In CKB tx, Connection is discribed in the Output.
     // pub client_id_on_a: CString,
@@ -104,14 +214,24 @@ pub struct MsgConnectionOpenInit {
 
 /// Per our convention, this message is sent to chain B.
 /// The handler will check proofs of chain A.
-#[derive(RlpDecodable, RlpEncodable)]
+#[derive(RlpDecodable, RlpEncodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgConnectionOpenTry {
     // pub client_id_on_b: CString,
-    // TODO: this field is useful when CKB is connecting to chains but Axon.
-    // pub client_state_of_b_on_a: Bytes,
     // pub counterparty: ConnectionCounterparty,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_init: Vec<u8>,
+    /// State of the client of chain B on chain A.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub client_state_of_b_on_a: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub proof_client_state_of_b_on_a: Vec<u8>,
+    pub consensus_height_of_b_on_a: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub consensus_state_of_b_on_a: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub proof_consensus_state_of_b_on_a: Vec<u8>,
     // pub counterparty_versions: Vec<CString>,
     // pub delay_period: u64,
     // deprecated
@@ -120,33 +240,47 @@ pub struct MsgConnectionOpenTry {
 
 /// Per our convention, this message is sent to chain A.
 /// The handler will check proofs of chain B.
-#[derive(RlpDecodable, RlpEncodable)]
+#[derive(RlpDecodable, RlpEncodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgConnectionOpenAck {
     // In CKB, IBC connection cells are stored in a a vector in a cell.
     // This message just convey the idx of the connection cell of it
     // and the content of that cell would be stored in witness of the tx.
     pub conn_id_on_a: usize,
     // pub conn_id_on_b: String,
-    // pub client_state_of_a_on_b: ClientState,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_try: Vec<u8>,
+    /// State of the client of chain A on chain B.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub client_state_of_a_on_b: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub proof_client_state_of_a_on_b: Vec<u8>,
+    pub consensus_height_of_a_on_b: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub consensus_state_of_a_on_b: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub proof_consensus_state_of_a_on_b: Vec<u8>,
     // pub version: CString,
 }
 
 /// Per our convention, this message is sent to chain B.
 /// The handler will check proofs of chain A.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgConnectionOpenConfirm {
     // In CKB, IBC connection cells are stored in a a vector in a cell.
     // This message just convey the idx of the connection cell of it
     // and the content of that cell would be stored in witness of the tx.
     pub conn_id_on_b: usize,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_ack: Vec<u8>,
 }
 
 // Per our convention, this message is sent to chain A
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelOpenInit {
     // pub port_id_on_a: CString,
     // pub connection_hops_on_a: Vec<CString>,
@@ -157,6 +291,7 @@ pub struct MsgChannelOpenInit {
 
 /// Per our convention, this message is sent to chain B.
 #[derive(RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelOpenTry {
     // pub port_id_on_b: CString,
     /* CKB's channel doesn't have this field */
@@ -164,6 +299,7 @@ pub struct MsgChannelOpenTry {
     // pub port_id_on_a: CString,
     // pub chain_id_on_a: CString,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_init: Vec<u8>,
     // pub ordering: Ordering,
     // pub connection_hops_on_a: Vec<String>,
@@ -173,11 +309,13 @@ pub struct MsgChannelOpenTry {
 
 /// Per our convention, this message is sent to chain A.
 #[derive(RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelOpenAck {
     /* In CKB tx, these 2 fields are found in cell dep and witness. */
     // pub port_id_on_a: CString,
     // pub chan_id_on_a: CString,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_try: Vec<u8>,
     // pub chain_id_on_b: CString,
     // pub connection_hops_on_b: Vec<String>,
@@ -185,17 +323,20 @@ pub struct MsgChannelOpenAck {
 
 /// Per our convention, this message is sent to chain B.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelOpenConfirm {
     // pub port_id_on_b: CString,
     // pub chain_id_on_b: CString,
     // pub channel_id: ChannelCounterparty,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_ack: Vec<u8>,
     // pub connection_hops_on_b: Vec<String>,
 }
 
 // Per our convention, this message is sent to chain A.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelCloseInit {
     /* In CKB tx, these 2 fields are found in witness. */
     // pub port_id_on_a: CString,
@@ -204,50 +345,63 @@ pub struct MsgChannelCloseInit {
 
 // Per our convention, this message is sent to chain B.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgChannelCloseConfirm {
     /* In CKB tx, these 2 fields are found in witness. */
     // pub port_id_on_b: CString,
     // pub port_id_on_b: CString,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_init: Vec<u8>,
 }
 
 // As our CKB convention, the content of the packet is stored in Witness.
 // We don't need to place it again in this message.
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgSendPacket {}
 
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgRecvPacket {
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_commitment: Vec<u8>,
 }
 
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgAckPacket {
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_acked: Vec<u8>,
 }
 
 // Business side sends this message after handling MsgRecvPacket
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgWriteAckPacket {}
 
 // If timeout block_number is set in Packet and reached, using MsgTimeoutPacket instead
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgTimeoutPacket {
     pub packet: Packet,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub next_sequence_recv: U256,
     pub proof_height: Height,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub proof_unreceived: Vec<u8>,
 }
 
 // It's additional msg type which isn't contained in IBC, and just used
 // in Business side to be consumed to obtain its capacity
 #[derive(RlpDecodable, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MsgConsumeAckPacket {}
 
 #[derive(RlpDecodable, RlpEncodable, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommitmentKV(pub H256, pub H256);
 
 impl CommitmentKV {
@@ -257,4 +411,69 @@ impl CommitmentKV {
             keccak_256(value.as_ref()).into(),
         )
     }
+
+    /// Deletion of the commitment at `path`, which has a zero value.
+    pub fn deletion(path: impl AsRef<[u8]>) -> Self {
+        Self(keccak_256(path.as_ref()).into(), H256::zero())
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        self.1.is_zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_commitments_in_any_order() {
+        let kvs = [CommitmentKV::hash("a", "1"), CommitmentKV::hash("b", "2")];
+        let reversed = [CommitmentKV::hash("b", "2"), CommitmentKV::hash("a", "1")];
+        (&kvs[..])
+            .write_commitments([("b", "2"), ("a", "1")])
+            .unwrap();
+        assert_eq!(
+            commitments_root(&kvs).unwrap(),
+            commitments_root(&reversed).unwrap()
+        );
+
+        assert!(matches!(
+            (&kvs[..]).write_commitments([("a", "1")]),
+            Err(VerifyError::Commitment)
+        ));
+        let duplicated = [CommitmentKV::hash("a", "1"), CommitmentKV::hash("a", "2")];
+        assert!(matches!(
+            (&duplicated[..]).write_commitments([("a", "1"), ("a", "2")]),
+            Err(VerifyError::DuplicateCommitmentPath)
+        ));
+    }
+
+    #[test]
+    fn test_envelope_commitments_root() {
+        let envelope = Envelope {
+            msg_type: MsgType::MsgSendPacket,
+            commitments: vec![CommitmentKV::hash("a", "1"), CommitmentKV::deletion("b")],
+            content: vec![1],
+            commitments_root: None,
+        };
+        // Same encoding as envelopes without the root field.
+        let mut s = rlp::RlpStream::new_list(3);
+        s.append(&envelope.msg_type)
+            .append_list(&envelope.commitments)
+            .append(&envelope.content);
+        assert_eq!(rlp::encode(&envelope), s.out());
+        envelope.verify_commitments_root().unwrap();
+
+        let mut envelope = envelope.with_commitments_root().unwrap();
+        let decoded: Envelope = rlp::decode(&rlp::encode(&envelope)).unwrap();
+        assert_eq!(decoded.commitments_root, envelope.commitments_root);
+        decoded.verify_commitments_root().unwrap();
+
+        envelope.commitments.pop();
+        assert!(matches!(
+            envelope.verify_commitments_root(),
+            Err(VerifyError::Commitment)
+        ));
+    }
 }
@@ -0,0 +1,479 @@
+//! Molecule encoding of IBC cell objects and envelopes.
+//!
+//! This is an alternative to RLP for CKB tooling. Readers generated from
+//! `mol/ibc.mol` verify the layout once and then read fields without decoding
+//! the whole object, e.g.
+//!
+//! ```ignore
+//! let channel = IbcChannelReader::from_slice(data)?;
+//! let state = State::try_from(channel.state().as_slice()[0]);
+//! ```
+//!
+//! Objects are converted to entities with `From` and decoded from readers
+//! with `TryFrom`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use molecule::prelude::{Builder, Entity, Reader};
+
+use crate::handler;
+use crate::message;
+use crate::object::{self, VerifyError};
+
+mod generated {
+    #![allow(
+        clippy::all,
+        clippy::indexing_slicing,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        dead_code
+    )]
+    include!(concat!(env!("OUT_DIR"), "/ibc.rs"));
+}
+
+pub use generated::*;
+
+fn uint64(v: u64) -> Uint64 {
+    Uint64::new_unchecked(v.to_le_bytes().to_vec().into())
+}
+
+fn byte32(v: &[u8; 32]) -> Byte32 {
+    Byte32::new_unchecked(v.to_vec().into())
+}
+
+fn bytes(v: &[u8]) -> Bytes {
+    Bytes::new_builder()
+        .set(v.iter().copied().map(Into::into).collect())
+        .build()
+}
+
+fn bytes_vec<T: AsRef<[u8]>>(v: &[T]) -> BytesVec {
+    BytesVec::new_builder()
+        .set(v.iter().map(|x| bytes(x.as_ref())).collect())
+        .build()
+}
+
+fn read_u64(r: Uint64Reader) -> u64 {
+    // Length is checked by the reader.
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(r.raw_data());
+    u64::from_le_bytes(buf)
+}
+
+fn height(v: &crate::proto::client::Height) -> Height {
+    Height::new_builder()
+        .revision_number(uint64(v.revision_number))
+        .revision_height(uint64(v.revision_height))
+        .build()
+}
+
+fn read_height(r: HeightReader) -> crate::proto::client::Height {
+    crate::proto::client::Height {
+        revision_number: read_u64(r.revision_number()),
+        revision_height: read_u64(r.revision_height()),
+    }
+}
+
+fn read_byte32(r: Byte32Reader) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf.copy_from_slice(r.raw_data());
+    buf
+}
+
+fn read_byte(slice: &[u8]) -> Result<u8, VerifyError> {
+    match slice {
+        [b] => Ok(*b),
+        _ => Err(VerifyError::SerdeError),
+    }
+}
+
+fn read_string(r: BytesReader) -> Result<String, VerifyError> {
+    String::from_utf8(r.raw_data().to_vec()).map_err(|_| VerifyError::SerdeError)
+}
+
+fn read_strings(r: BytesVecReader) -> Result<Vec<String>, VerifyError> {
+    r.iter().map(read_string).collect()
+}
+
+fn read_enum<T: TryFrom<u8>>(slice: &[u8]) -> Result<T, VerifyError> {
+    T::try_from(read_byte(slice)?).map_err(|_| VerifyError::SerdeError)
+}
+
+impl From<&object::Version> for Version {
+    fn from(v: &object::Version) -> Self {
+        Self::new_builder()
+            .identifier(bytes(v.identifier.as_bytes()))
+            .features(bytes_vec(&v.features))
+            .build()
+    }
+}
+
+impl TryFrom<VersionReader<'_>> for object::Version {
+    type Error = VerifyError;
+
+    fn try_from(r: VersionReader<'_>) -> Result<Self, VerifyError> {
+        Ok(Self {
+            identifier: read_string(r.identifier())?,
+            features: read_strings(r.features())?,
+        })
+    }
+}
+
+impl From<&object::ConnectionEnd> for ConnectionEnd {
+    fn from(v: &object::ConnectionEnd) -> Self {
+        let counterparty = ConnectionCounterparty::new_builder()
+            .client_id(bytes(v.counterparty.client_id.as_bytes()))
+            .connection_id(bytes(v.counterparty.connection_id.as_bytes()))
+            .commitment_prefix(bytes(&v.counterparty.commitment_prefix))
+            .build();
+        Self::new_builder()
+            .state((v.state as u8).into())
+            .counterparty(counterparty)
+            .delay_period(uint64(v.delay_period))
+            .versions(
+                VersionVec::new_builder()
+                    .set(v.versions.iter().map(Into::into).collect())
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl TryFrom<ConnectionEndReader<'_>> for object::ConnectionEnd {
+    type Error = VerifyError;
+
+    fn try_from(r: ConnectionEndReader<'_>) -> Result<Self, VerifyError> {
+        let counterparty = r.counterparty();
+        Ok(Self {
+            state: read_enum(r.state().as_slice())?,
+            counterparty: object::ConnectionCounterparty {
+                client_id: read_string(counterparty.client_id())?,
+                connection_id: read_string(counterparty.connection_id())?,
+                commitment_prefix: counterparty.commitment_prefix().raw_data().to_vec(),
+            },
+            delay_period: read_u64(r.delay_period()),
+            versions: r
+                .versions()
+                .iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<&handler::IbcConnections> for IbcConnections {
+    fn from(v: &handler::IbcConnections) -> Self {
+        Self::new_builder()
+            .next_channel_number(uint64(v.next_channel_number))
+            .connections(
+                ConnectionEndVec::new_builder()
+                    .set(v.connections.iter().map(Into::into).collect())
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl TryFrom<IbcConnectionsReader<'_>> for handler::IbcConnections {
+    type Error = VerifyError;
+
+    fn try_from(r: IbcConnectionsReader<'_>) -> Result<Self, VerifyError> {
+        Ok(Self {
+            next_channel_number: read_u64(r.next_channel_number()),
+            connections: r
+                .connections()
+                .iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<&handler::IbcChannel> for IbcChannel {
+    fn from(v: &handler::IbcChannel) -> Self {
+        let sequence = Sequence::new_builder()
+            .next_sequence_sends(uint64(v.sequence.next_sequence_sends))
+            .next_sequence_recvs(uint64(v.sequence.next_sequence_recvs))
+            .next_sequence_acks(uint64(v.sequence.next_sequence_acks))
+            .received_sequences(
+                Uint64Vec::new_builder()
+                    .set(
+                        v.sequence
+                            .received_sequences
+                            .iter()
+                            .copied()
+                            .map(uint64)
+                            .collect(),
+                    )
+                    .build(),
+            )
+            .build();
+        let counterparty = ChannelCounterparty::new_builder()
+            .port_id(bytes(v.counterparty.port_id.as_bytes()))
+            .channel_id(bytes(v.counterparty.channel_id.as_bytes()))
+            .connection_id(bytes(v.counterparty.connection_id.as_bytes()))
+            .build();
+        Self::new_builder()
+            .number(uint64(v.number))
+            .port_id(bytes(v.port_id.as_bytes()))
+            .state((v.state as u8).into())
+            .order((v.order as u8).into())
+            .sequence(sequence)
+            .counterparty(counterparty)
+            .connection_hops(bytes_vec(&v.connection_hops))
+            .version(bytes(v.version.as_bytes()))
+            .build()
+    }
+}
+
+impl TryFrom<IbcChannelReader<'_>> for handler::IbcChannel {
+    type Error = VerifyError;
+
+    fn try_from(r: IbcChannelReader<'_>) -> Result<Self, VerifyError> {
+        let sequence = r.sequence();
+        let counterparty = r.counterparty();
+        Ok(Self {
+            number: read_u64(r.number()),
+            port_id: read_string(r.port_id())?,
+            state: read_enum(r.state().as_slice())?,
+            order: read_enum(r.order().as_slice())?,
+            sequence: handler::Sequence {
+                next_sequence_sends: read_u64(sequence.next_sequence_sends()),
+                next_sequence_recvs: read_u64(sequence.next_sequence_recvs()),
+                next_sequence_acks: read_u64(sequence.next_sequence_acks()),
+                received_sequences: sequence.received_sequences().iter().map(read_u64).collect(),
+            },
+            counterparty: object::ChannelCounterparty {
+                port_id: read_string(counterparty.port_id())?,
+                channel_id: read_string(counterparty.channel_id())?,
+                connection_id: read_string(counterparty.connection_id())?,
+            },
+            connection_hops: read_strings(r.connection_hops())?,
+            version: read_string(r.version())?,
+        })
+    }
+}
+
+impl From<&object::Packet> for Packet {
+    fn from(v: &object::Packet) -> Self {
+        Self::new_builder()
+            .sequence(uint64(v.sequence))
+            .source_port_id(bytes(v.source_port_id.as_bytes()))
+            .source_channel_id(bytes(v.source_channel_id.as_bytes()))
+            .destination_port_id(bytes(v.destination_port_id.as_bytes()))
+            .destination_channel_id(bytes(v.destination_channel_id.as_bytes()))
+            .data(bytes(&v.data))
+            .timeout_height(height(&v.timeout_height))
+            .timeout_timestamp(uint64(v.timeout_timestamp))
+            .build()
+    }
+}
+
+impl TryFrom<PacketReader<'_>> for object::Packet {
+    type Error = VerifyError;
+
+    fn try_from(r: PacketReader<'_>) -> Result<Self, VerifyError> {
+        Ok(Self {
+            sequence: read_u64(r.sequence()),
+            source_port_id: read_string(r.source_port_id())?,
+            source_channel_id: read_string(r.source_channel_id())?,
+            destination_port_id: read_string(r.destination_port_id())?,
+            destination_channel_id: read_string(r.destination_channel_id())?,
+            data: r.data().raw_data().to_vec(),
+            timeout_height: read_height(r.timeout_height()),
+            timeout_timestamp: read_u64(r.timeout_timestamp()),
+        })
+    }
+}
+
+impl From<&handler::IbcPacket> for IbcPacket {
+    fn from(v: &handler::IbcPacket) -> Self {
+        Self::new_builder()
+            .packet((&v.packet).into())
+            .status((v.status as u8).into())
+            .ack(
+                BytesOpt::new_builder()
+                    .set(v.ack.as_deref().map(bytes))
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl TryFrom<IbcPacketReader<'_>> for handler::IbcPacket {
+    type Error = VerifyError;
+
+    fn try_from(r: IbcPacketReader<'_>) -> Result<Self, VerifyError> {
+        Ok(Self {
+            packet: r.packet().try_into()?,
+            status: read_enum(r.status().as_slice())?,
+            ack: r.ack().to_opt().map(|ack| ack.raw_data().to_vec()),
+        })
+    }
+}
+
+impl From<&message::Envelope> for Envelope {
+    fn from(v: &message::Envelope) -> Self {
+        let commitments = v
+            .commitments
+            .iter()
+            .map(|kv| {
+                CommitmentKV::new_builder()
+                    .path(byte32(kv.0.as_fixed_bytes()))
+                    .value(byte32(kv.1.as_fixed_bytes()))
+                    .build()
+            })
+            .collect();
+        Self::new_builder()
+            .msg_type((v.msg_type as u8).into())
+            .commitments(CommitmentKVVec::new_builder().set(commitments).build())
+            .content(bytes(&v.content))
+            .commitments_root(
+                Byte32Opt::new_builder()
+                    .set(v.commitments_root.map(|root| byte32(root.as_fixed_bytes())))
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl TryFrom<EnvelopeReader<'_>> for message::Envelope {
+    type Error = VerifyError;
+
+    fn try_from(r: EnvelopeReader<'_>) -> Result<Self, VerifyError> {
+        Ok(Self {
+            msg_type: read_enum(r.msg_type().as_slice())?,
+            commitments: r
+                .commitments()
+                .iter()
+                .map(|kv| {
+                    message::CommitmentKV(
+                        read_byte32(kv.path()).into(),
+                        read_byte32(kv.value()).into(),
+                    )
+                })
+                .collect(),
+            content: r.content().raw_data().to_vec(),
+            commitments_root: r
+                .commitments_root()
+                .to_opt()
+                .map(|root| read_byte32(root).into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{Ordering, State};
+
+    #[test]
+    fn test_ibc_connections() {
+        let v = handler::IbcConnections {
+            next_channel_number: 3,
+            connections: vec![
+                object::ConnectionEnd {
+                    state: State::Open,
+                    counterparty: object::ConnectionCounterparty {
+                        client_id: "client".into(),
+                        connection_id: "connection-2".into(),
+                        ..Default::default()
+                    },
+                    delay_period: 7,
+                    ..Default::default()
+                },
+                object::ConnectionEnd::default(),
+            ],
+        };
+        let data = IbcConnections::from(&v).as_slice().to_vec();
+        let reader = IbcConnectionsReader::from_slice(&data).unwrap();
+        assert_eq!(handler::IbcConnections::try_from(reader).unwrap(), v);
+
+        // Fields are read without decoding the whole object.
+        assert_eq!(read_u64(reader.next_channel_number()), 3);
+        assert_eq!(reader.connections().len(), 2);
+    }
+
+    #[test]
+    fn test_ibc_channel() {
+        let mut v = handler::IbcChannel {
+            number: 5,
+            state: State::OpenTry,
+            order: Ordering::Ordered,
+            connection_hops: vec!["connection-0".into()],
+            version: "ics20-1".into(),
+            ..Default::default()
+        };
+        v.sequence.received_sequences = vec![1, 3];
+        v.counterparty.channel_id = "channel-9".into();
+        let data = IbcChannel::from(&v).as_slice().to_vec();
+        let reader = IbcChannelReader::from_slice(&data).unwrap();
+        assert_eq!(handler::IbcChannel::try_from(reader).unwrap(), v);
+        assert_eq!(
+            read_enum::<State>(reader.state().as_slice()).unwrap(),
+            State::OpenTry
+        );
+    }
+
+    #[test]
+    fn test_ibc_packet() {
+        for ack in [None, Some(vec![]), Some(vec![1, 2])] {
+            let v = handler::IbcPacket {
+                packet: object::Packet {
+                    sequence: 2,
+                    data: vec![3; 40],
+                    timeout_height: crate::proto::client::Height {
+                        revision_number: 1,
+                        revision_height: 100,
+                    },
+                    ..Default::default()
+                },
+                status: handler::PacketStatus::WriteAck,
+                ack,
+            };
+            let data = IbcPacket::from(&v).as_slice().to_vec();
+            let reader = IbcPacketReader::from_slice(&data).unwrap();
+            assert_eq!(handler::IbcPacket::try_from(reader).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn test_envelope() {
+        let v = message::Envelope {
+            msg_type: message::MsgType::MsgRecvPacket,
+            commitments: vec![message::CommitmentKV::hash("a", "b")],
+            content: vec![1, 2, 3],
+            commitments_root: None,
+        };
+        let data = Envelope::from(&v).as_slice().to_vec();
+        let reader = EnvelopeReader::from_slice(&data).unwrap();
+        let decoded = message::Envelope::try_from(reader).unwrap();
+        assert_eq!(rlp::encode(&decoded), rlp::encode(&v));
+
+        let v = v.with_commitments_root().unwrap();
+        let data = Envelope::from(&v).as_slice().to_vec();
+        let reader = EnvelopeReader::from_slice(&data).unwrap();
+        let decoded = message::Envelope::try_from(reader).unwrap();
+        assert_eq!(decoded.commitments_root, v.commitments_root);
+    }
+
+    #[test]
+    fn test_invalid_enum() {
+        let data = IbcPacket::from(&handler::IbcPacket {
+            packet: Default::default(),
+            status: handler::PacketStatus::Send,
+            ack: None,
+        })
+        .as_builder()
+        .status(0.into())
+        .build()
+        .as_slice()
+        .to_vec();
+        assert!(matches!(
+            handler::IbcPacket::try_from(IbcPacketReader::from_slice(&data).unwrap()),
+            Err(VerifyError::SerdeError)
+        ));
+    }
+}
@@ -1,4 +1,6 @@
+use crate::connection_id;
 use crate::consts::COMMITMENT_PREFIX;
+use crate::host::{ClientId, ConnectionId};
 use crate::proto;
 use crate::Bytes;
 use crate::ChannelArgs;
@@ -6,6 +8,7 @@ use alloc::borrow::ToOwned;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::str::FromStr;
 use rlp_derive::RlpDecodable;
 use rlp_derive::RlpEncodable;
 
@@ -49,6 +52,18 @@ pub enum VerifyError {
 
     Commitment,
     Mpt,
+
+    ZeroIBCHandlerAddress,
+    WrongCellData,
+    DuplicateCommitmentPath,
+    Smt,
+    ClientFrozen,
+    ClientExpired,
+    InvalidProofHeight,
+    InvalidSelfClient,
+    WrongMetadataCell,
+    PacketNotTimedOut,
+    UntrustedHeader,
 }
 
 impl From<VerifyError> for i8 {
@@ -65,6 +80,7 @@ impl From<rlp::DecoderError> for VerifyError {
 
 impl_enum_rlp!(
     #[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(u8)]
     pub enum State {
         #[default]
@@ -96,10 +112,32 @@ impl State {
             _ => proto::channel::State::UninitializedUnspecified,
         }
     }
+
+    pub fn from_proto_connection_state(state: i32) -> Result<Self, VerifyError> {
+        match proto::connection::State::try_from(state) {
+            Ok(proto::connection::State::UninitializedUnspecified) => Ok(State::Unknown),
+            Ok(proto::connection::State::Init) => Ok(State::Init),
+            Ok(proto::connection::State::Tryopen) => Ok(State::OpenTry),
+            Ok(proto::connection::State::Open) => Ok(State::Open),
+            Err(_) => Err(VerifyError::WrongConnectionState),
+        }
+    }
+
+    pub fn from_proto_channel_state(state: i32) -> Result<Self, VerifyError> {
+        match proto::channel::State::try_from(state) {
+            Ok(proto::channel::State::UninitializedUnspecified) => Ok(State::Unknown),
+            Ok(proto::channel::State::Init) => Ok(State::Init),
+            Ok(proto::channel::State::Tryopen) => Ok(State::OpenTry),
+            Ok(proto::channel::State::Open) => Ok(State::Open),
+            Ok(proto::channel::State::Closed) => Ok(State::Closed),
+            Err(_) => Err(VerifyError::WrongChannelState),
+        }
+    }
 }
 
 impl_enum_rlp!(
     #[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(u8)]
     pub enum Ordering {
         #[default]
@@ -120,10 +158,23 @@ impl From<Ordering> for proto::channel::Order {
     }
 }
 
+impl Ordering {
+    pub fn from_proto(order: i32) -> Result<Self, VerifyError> {
+        match proto::channel::Order::try_from(order) {
+            Ok(proto::channel::Order::NoneUnspecified) => Ok(Ordering::Unknown),
+            Ok(proto::channel::Order::Unordered) => Ok(Ordering::Unordered),
+            Ok(proto::channel::Order::Ordered) => Ok(Ordering::Ordered),
+            Err(_) => Err(VerifyError::WrongChannel),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectionCounterparty {
     pub client_id: String,
     pub connection_id: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub commitment_prefix: Bytes,
 }
 
@@ -138,24 +189,58 @@ impl Default for ConnectionCounterparty {
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelCounterparty {
     pub port_id: String,
     pub channel_id: String,
     pub connection_id: String,
 }
 
-#[derive(Clone, PartialEq, Eq, RlpEncodable, RlpDecodable, Debug)]
+#[derive(Clone, PartialEq, Eq, RlpEncodable, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Packet {
     pub sequence: u64,
     pub source_port_id: String,
     pub source_channel_id: String,
     pub destination_port_id: String,
     pub destination_channel_id: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
     pub data: Vec<u8>,
-    pub timeout_height: u64,
+    pub timeout_height: proto::client::Height,
     pub timeout_timestamp: u64,
 }
 
+/// Packets encoded before timeout heights had revision numbers have a plain
+/// `u64` timeout height, which is decoded as a height on revision 0. They are
+/// encoded back with a full height, so cells of these packets are checked
+/// against the witness bytes with `CellObject::from_witness`.
+impl rlp::Decodable for Packet {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        if rlp.item_count()? != 8 {
+            return Err(rlp::DecoderError::RlpIncorrectListLen);
+        }
+        let timeout_height = rlp.at(6)?;
+        let timeout_height = if timeout_height.is_list() {
+            timeout_height.as_val()?
+        } else {
+            proto::client::Height {
+                revision_number: 0,
+                revision_height: timeout_height.as_val()?,
+            }
+        };
+        Ok(Self {
+            sequence: rlp.val_at(0)?,
+            source_port_id: rlp.val_at(1)?,
+            source_channel_id: rlp.val_at(2)?,
+            destination_port_id: rlp.val_at(3)?,
+            destination_channel_id: rlp.val_at(4)?,
+            data: rlp.val_at(5)?,
+            timeout_height,
+            timeout_timestamp: rlp.val_at(7)?,
+        })
+    }
+}
+
 impl Default for Packet {
     fn default() -> Self {
         Self {
@@ -165,13 +250,49 @@ impl Default for Packet {
             destination_port_id: ChannelArgs::default().port_id_str(),
             destination_channel_id: ChannelArgs::default().channel_id_str(),
             data: Default::default(),
-            timeout_height: 0,
+            timeout_height: Default::default(),
             timeout_timestamp: 0,
         }
     }
 }
 
+impl From<Packet> for proto::channel::Packet {
+    fn from(value: Packet) -> Self {
+        Self {
+            sequence: value.sequence,
+            source_port: value.source_port_id,
+            source_channel: value.source_channel_id,
+            destination_port: value.destination_port_id,
+            destination_channel: value.destination_channel_id,
+            data: value.data,
+            timeout_height: Some(value.timeout_height),
+            timeout_timestamp: value.timeout_timestamp,
+        }
+    }
+}
+
+impl TryFrom<proto::channel::Packet> for Packet {
+    type Error = VerifyError;
+
+    fn try_from(value: proto::channel::Packet) -> Result<Self, VerifyError> {
+        if value.sequence == 0 {
+            return Err(VerifyError::WrongPacketSequence);
+        }
+        Ok(Self {
+            sequence: value.sequence,
+            source_port_id: value.source_port,
+            source_channel_id: value.source_channel,
+            destination_port_id: value.destination_port,
+            destination_channel_id: value.destination_channel,
+            data: value.data,
+            timeout_height: value.timeout_height.unwrap_or_default(),
+            timeout_timestamp: value.timeout_timestamp,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     pub identifier: String,
     pub features: Vec<String>,
@@ -195,7 +316,17 @@ impl From<Version> for proto::connection::Version {
     }
 }
 
+impl From<proto::connection::Version> for Version {
+    fn from(value: proto::connection::Version) -> Self {
+        Self {
+            identifier: value.identifier,
+            features: value.features,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, RlpEncodable, RlpDecodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectionEnd {
     pub state: State,
     pub counterparty: ConnectionCounterparty,
@@ -230,4 +361,196 @@ impl ConnectionEnd {
             delay_period: self.delay_period,
         }
     }
+
+    pub fn to_identified_proto(
+        self,
+        client_id: String,
+        connection_idx: usize,
+    ) -> proto::connection::IdentifiedConnection {
+        let id = connection_id(&client_id, connection_idx);
+        let connection = self.to_proto(client_id);
+        proto::connection::IdentifiedConnection {
+            id,
+            client_id: connection.client_id,
+            versions: connection.versions,
+            state: connection.state,
+            counterparty: connection.counterparty,
+            delay_period: connection.delay_period,
+        }
+    }
+}
+
+/// The client id is dropped. It is determined by the connection args.
+impl TryFrom<proto::connection::ConnectionEnd> for ConnectionEnd {
+    type Error = VerifyError;
+
+    fn try_from(value: proto::connection::ConnectionEnd) -> Result<Self, VerifyError> {
+        let counterparty = value
+            .counterparty
+            .ok_or(VerifyError::WrongConnectionCounterparty)?;
+        Ok(Self {
+            state: State::from_proto_connection_state(value.state)?,
+            counterparty: ConnectionCounterparty {
+                client_id: counterparty.client_id,
+                connection_id: counterparty.connection_id,
+                commitment_prefix: counterparty
+                    .prefix
+                    .ok_or(VerifyError::WrongConnectionCounterparty)?
+                    .key_prefix,
+            },
+            delay_period: value.delay_period,
+            versions: value.versions.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+/// The id must be the connection id of the client id.
+impl TryFrom<proto::connection::IdentifiedConnection> for ConnectionEnd {
+    type Error = VerifyError;
+
+    fn try_from(value: proto::connection::IdentifiedConnection) -> Result<Self, VerifyError> {
+        let client_id = ClientId::from_str(&value.client_id)?;
+        ConnectionId::from_str(&value.id)?
+            .index_of(&client_id)
+            .ok_or(VerifyError::WrongConnectionId)?;
+        proto::connection::ConnectionEnd {
+            client_id: value.client_id,
+            versions: value.versions,
+            state: value.state,
+            counterparty: value.counterparty,
+            delay_period: value.delay_period,
+        }
+        .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_conversion() {
+        let packet = Packet {
+            sequence: 4,
+            data: vec![1, 2],
+            timeout_height: proto::client::Height {
+                revision_number: 1,
+                revision_height: 30,
+            },
+            timeout_timestamp: 40,
+            ..Default::default()
+        };
+        let encoded = proto::channel::Packet::from(packet.clone());
+        assert_eq!(encoded.timeout_height, Some(packet.timeout_height));
+        assert_eq!(Packet::try_from(encoded.clone()).unwrap(), packet);
+
+        let mut wrong = encoded;
+        wrong.sequence = 0;
+        assert!(matches!(
+            Packet::try_from(wrong),
+            Err(VerifyError::WrongPacketSequence)
+        ));
+    }
+
+    #[test]
+    fn test_packet_rlp() {
+        let packet = Packet {
+            sequence: 4,
+            data: vec![1, 2],
+            timeout_height: proto::client::Height {
+                revision_number: 2,
+                revision_height: 30,
+            },
+            timeout_timestamp: 40,
+            ..Default::default()
+        };
+        let decoded: Packet = rlp::decode(&rlp::encode(&packet)).unwrap();
+        assert_eq!(decoded, packet);
+
+        // Legacy encoding with a plain timeout height.
+        let mut s = rlp::RlpStream::new_list(8);
+        s.append(&packet.sequence)
+            .append(&packet.source_port_id)
+            .append(&packet.source_channel_id)
+            .append(&packet.destination_port_id)
+            .append(&packet.destination_channel_id)
+            .append(&packet.data)
+            .append(&30u64)
+            .append(&packet.timeout_timestamp);
+        let decoded: Packet = rlp::decode(&s.out()).unwrap();
+        assert_eq!(decoded.timeout_height.revision_number, 0);
+        assert_eq!(decoded.timeout_height.revision_height, 30);
+
+        let mut s = rlp::RlpStream::new_list(7);
+        s.append(&1u64);
+        for _ in 0..6 {
+            s.append(&0u64);
+        }
+        assert!(rlp::decode::<Packet>(&s.out()).is_err());
+    }
+
+    #[test]
+    fn test_connection_end_conversion() {
+        let connection = ConnectionEnd {
+            state: State::OpenTry,
+            counterparty: ConnectionCounterparty {
+                client_id: "client".into(),
+                connection_id: "connection-3".into(),
+                ..Default::default()
+            },
+            delay_period: 5,
+            ..Default::default()
+        };
+        let encoded = connection.clone().to_proto("0123456789".into());
+        assert_eq!(
+            ConnectionEnd::try_from(encoded.clone()).unwrap(),
+            connection
+        );
+
+        let mut wrong = encoded.clone();
+        wrong.counterparty = None;
+        assert!(matches!(
+            ConnectionEnd::try_from(wrong),
+            Err(VerifyError::WrongConnectionCounterparty)
+        ));
+        let mut wrong = encoded;
+        wrong.state = 4;
+        assert!(matches!(
+            ConnectionEnd::try_from(wrong),
+            Err(VerifyError::WrongConnectionState)
+        ));
+
+        let mut identified = connection
+            .clone()
+            .to_identified_proto("0123456789".into(), 2);
+        assert_eq!(identified.id, "012345-connection-2");
+        assert_eq!(
+            ConnectionEnd::try_from(identified.clone()).unwrap(),
+            connection
+        );
+        identified.id = "012345-connection-x".into();
+        assert!(matches!(
+            ConnectionEnd::try_from(identified),
+            Err(VerifyError::WrongConnectionId)
+        ));
+    }
+
+    #[test]
+    fn test_state_and_ordering_conversion() {
+        for state in [
+            State::Unknown,
+            State::Init,
+            State::OpenTry,
+            State::Open,
+            State::Closed,
+        ] {
+            let encoded = state.proto_channel_state() as i32;
+            assert_eq!(State::from_proto_channel_state(encoded).unwrap(), state);
+        }
+        for order in [Ordering::Unknown, Ordering::Unordered, Ordering::Ordered] {
+            let encoded = proto::channel::Order::from(order) as i32;
+            assert_eq!(Ordering::from_proto(encoded).unwrap(), order);
+        }
+        assert!(Ordering::from_proto(3).is_err());
+    }
 }
@@ -12,4 +12,41 @@ pub mod commitment {
 
 pub mod client {
     include!(concat!(env!("OUT_DIR"), "/client.rs"));
+
+    /// Heights are ordered by revision number, then by revision height.
+    impl Height {
+        pub fn new(revision_number: u64, revision_height: u64) -> Self {
+            Self {
+                revision_number,
+                revision_height,
+            }
+        }
+
+        pub fn is_zero(&self) -> bool {
+            self.revision_number == 0 && self.revision_height == 0
+        }
+
+        /// The next height on the same revision.
+        pub fn increment(self) -> Option<Self> {
+            Some(Self::new(
+                self.revision_number,
+                self.revision_height.checked_add(1)?,
+            ))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_height_ordering() {
+            assert!(Height::new(0, 10) < Height::new(0, 11));
+            assert!(Height::new(0, 100) < Height::new(1, 1));
+            assert_eq!(Height::new(1, 1).max(Height::new(0, 5)), Height::new(1, 1));
+            assert_eq!(Height::new(2, 3).increment(), Some(Height::new(2, 4)));
+            assert_eq!(Height::new(2, u64::MAX).increment(), None);
+            assert!(Height::default().is_zero());
+        }
+    }
 }
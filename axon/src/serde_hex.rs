@@ -0,0 +1,317 @@
+//! Serde helpers that encode byte fields as 0x-prefixed hex strings, the same
+//! as `H256` in `ethereum-types`.
+//!
+//! Use with `#[serde(with = "crate::serde_hex")]`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+fn encode(v: &[u8]) -> String {
+    format!("0x{}", hex::encode(v))
+}
+
+fn decode<T: TryFrom<Vec<u8>>, E: Error>(s: &str) -> Result<T, E> {
+    let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(E::custom)?;
+    T::try_from(bytes).map_err(|_| E::custom("invalid length"))
+}
+
+pub fn serialize<T: AsRef<[u8]>, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&encode(v.as_ref()))
+}
+
+pub fn deserialize<'de, T: TryFrom<Vec<u8>>, D: Deserializer<'de>>(d: D) -> Result<T, D::Error> {
+    decode(&String::deserialize(d)?)
+}
+
+/// Same as the parent module, but for optional fields. `None` is `null`.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<T: AsRef<[u8]>, S: Serializer>(
+        v: &Option<T>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => s.serialize_some(&encode(v.as_ref())),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T: TryFrom<Vec<u8>>, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<T>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|s| decode(&s))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::json;
+
+    use crate::handler::*;
+    use crate::message::*;
+    use crate::object::*;
+    use crate::proto::client::Height;
+    use crate::{ChannelArgs, ConnectionArgs, PacketArgs};
+
+    /// Check the JSON form of `v`, and that decoding it gives the same RLP.
+    fn check<T>(v: &T, expected: serde_json::Value)
+    where
+        T: Serialize + DeserializeOwned + rlp::Encodable,
+    {
+        let json = serde_json::to_value(v).unwrap();
+        assert_eq!(json, expected);
+        let decoded: T = serde_json::from_value(json).unwrap();
+        assert_eq!(rlp::encode(&decoded), rlp::encode(v));
+    }
+
+    fn height() -> Height {
+        Height {
+            revision_number: 0,
+            revision_height: 18,
+        }
+    }
+
+    #[test]
+    fn test_ibc_connections_json() {
+        let connections = IbcConnections {
+            next_channel_number: 2,
+            connections: vec![ConnectionEnd {
+                state: State::OpenTry,
+                counterparty: ConnectionCounterparty {
+                    client_id: "client".into(),
+                    connection_id: "connection-0".into(),
+                    ..Default::default()
+                },
+                delay_period: 0,
+                versions: vec![Version::version_1()],
+            }],
+        };
+        check(
+            &connections,
+            json!({
+                "next_channel_number": 2,
+                "connections": [{
+                    "state": "OpenTry",
+                    "counterparty": {
+                        "client_id": "client",
+                        "connection_id": "connection-0",
+                        "commitment_prefix": "0x696263",
+                    },
+                    "delay_period": 0,
+                    "versions": [{
+                        "identifier": "1",
+                        "features": ["ORDER_ORDERED", "ORDER_UNORDERED"],
+                    }],
+                }],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_ibc_channel_json() {
+        let channel = IbcChannel {
+            number: 1,
+            port_id: "transfer".into(),
+            state: State::Open,
+            order: Ordering::Unordered,
+            sequence: Sequence {
+                next_sequence_sends: 3,
+                next_sequence_recvs: 1,
+                next_sequence_acks: 2,
+                received_sequences: vec![1, 2],
+            },
+            counterparty: ChannelCounterparty {
+                port_id: "transfer".into(),
+                channel_id: "channel-4".into(),
+                connection_id: "connection-0".into(),
+            },
+            connection_hops: vec!["connection-1".into()],
+            version: "ics20-1".into(),
+        };
+        check(
+            &channel,
+            json!({
+                "number": 1,
+                "port_id": "transfer",
+                "state": "Open",
+                "order": "Unordered",
+                "sequence": {
+                    "next_sequence_sends": 3,
+                    "next_sequence_recvs": 1,
+                    "next_sequence_acks": 2,
+                    "received_sequences": [1, 2],
+                },
+                "counterparty": {
+                    "port_id": "transfer",
+                    "channel_id": "channel-4",
+                    "connection_id": "connection-0",
+                },
+                "connection_hops": ["connection-1"],
+                "version": "ics20-1",
+            }),
+        );
+    }
+
+    #[test]
+    fn test_ibc_packet_json() {
+        let mut packet = IbcPacket {
+            packet: Packet {
+                sequence: 7,
+                source_port_id: "transfer".into(),
+                source_channel_id: "channel-0".into(),
+                destination_port_id: "transfer".into(),
+                destination_channel_id: "channel-1".into(),
+                data: vec![0xab, 0xcd],
+                timeout_height: Height {
+                    revision_number: 1,
+                    revision_height: 100,
+                },
+                timeout_timestamp: 0,
+            },
+            status: PacketStatus::Send,
+            ack: None,
+        };
+        let packet_json = json!({
+            "sequence": 7,
+            "source_port_id": "transfer",
+            "source_channel_id": "channel-0",
+            "destination_port_id": "transfer",
+            "destination_channel_id": "channel-1",
+            "data": "0xabcd",
+            "timeout_height": { "revision_number": 1, "revision_height": 100 },
+            "timeout_timestamp": 0,
+        });
+        check(
+            &packet,
+            json!({ "packet": packet_json, "status": "Send", "ack": null }),
+        );
+
+        packet.status = PacketStatus::WriteAck;
+        packet.ack = Some(vec![1]);
+        check(
+            &packet,
+            json!({ "packet": packet_json, "status": "WriteAck", "ack": "0x01" }),
+        );
+    }
+
+    #[test]
+    fn test_envelope_json() {
+        let envelope = Envelope {
+            msg_type: MsgType::MsgRecvPacket,
+            commitments: vec![CommitmentKV([1; 32].into(), [2; 32].into())],
+            content: rlp::encode(&MsgRecvPacket {
+                proof_height: height(),
+                proof_commitment: vec![3, 4],
+            })
+            .to_vec(),
+            commitments_root: None,
+        };
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["msg_type"], "MsgRecvPacket");
+        assert_eq!(json["commitments"][0][0], format!("0x{}", "01".repeat(32)));
+        assert!(json.get("commitments_root").is_none());
+        let decoded: Envelope = serde_json::from_value(json).unwrap();
+        assert_eq!(rlp::encode(&decoded), rlp::encode(&envelope));
+
+        let envelope = envelope.with_commitments_root().unwrap();
+        let json = serde_json::to_value(&envelope).unwrap();
+        let decoded: Envelope = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.commitments_root, envelope.commitments_root);
+
+        check(
+            &MsgRecvPacket {
+                proof_height: height(),
+                proof_commitment: vec![3, 4],
+            },
+            json!({
+                "proof_height": { "revision_number": 0, "revision_height": 18 },
+                "proof_commitment": "0x0304",
+            }),
+        );
+    }
+
+    #[test]
+    fn test_msgs_json() {
+        check(
+            &MsgConnectionOpenAck {
+                conn_id_on_a: 3,
+                proof_height: height(),
+                proof_try: vec![],
+                client_state_of_a_on_b: vec![1],
+                proof_client_state_of_a_on_b: vec![2],
+                consensus_height_of_a_on_b: height(),
+                consensus_state_of_a_on_b: vec![3],
+                proof_consensus_state_of_a_on_b: vec![4],
+            },
+            json!({
+                "conn_id_on_a": 3,
+                "proof_height": { "revision_number": 0, "revision_height": 18 },
+                "proof_try": "0x",
+                "client_state_of_a_on_b": "0x01",
+                "proof_client_state_of_a_on_b": "0x02",
+                "consensus_height_of_a_on_b": { "revision_number": 0, "revision_height": 18 },
+                "consensus_state_of_a_on_b": "0x03",
+                "proof_consensus_state_of_a_on_b": "0x04",
+            }),
+        );
+        let msg = MsgTimeoutPacket {
+            packet: Packet::default(),
+            next_sequence_recv: vec![5],
+            proof_height: height(),
+            proof_unreceived: vec![6],
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["next_sequence_recv"], "0x05");
+        assert_eq!(json["proof_unreceived"], "0x06");
+        check(&msg, json);
+        check(&MsgClientCreate {}, json!({}));
+    }
+
+    #[test]
+    fn test_args_json() {
+        let args = PacketArgs {
+            ibc_handler_address: [1; 20],
+            channel_id: 2,
+            port_id: [3; 32],
+            sequence: 4,
+        };
+        let json = serde_json::to_value(args).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "ibc_handler_address": format!("0x{}", "01".repeat(20)),
+                "channel_id": 2,
+                "port_id": format!("0x{}", "03".repeat(32)),
+                "sequence": 4,
+            })
+        );
+        assert_eq!(serde_json::from_value::<PacketArgs>(json).unwrap(), args);
+
+        let args = ChannelArgs {
+            open: true,
+            channel_id: 9,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&args).unwrap();
+        assert_eq!(serde_json::from_str::<ChannelArgs>(&json).unwrap(), args);
+
+        let args = ConnectionArgs::default();
+        let json = serde_json::to_string(&args).unwrap();
+        assert_eq!(serde_json::from_str::<ConnectionArgs>(&json).unwrap(), args);
+
+        // Wrong length.
+        assert!(serde_json::from_value::<ConnectionArgs>(json!({
+            "metadata_type_id": "0x00",
+            "ibc_handler_address": format!("0x{}", "00".repeat(20)),
+        }))
+        .is_err());
+    }
+}
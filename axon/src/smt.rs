@@ -0,0 +1,287 @@
+//! Sparse Merkle tree of all IBC commitments on CKB.
+//!
+//! Leaves are `CommitmentKV`s: keys are path hashes and values are value
+//! hashes, so a deletion (zero value) removes the leaf. The root is the data
+//! of a dedicated cell, and every handler updates it through
+//! `WriteOrVerifyCommitments` with `SmtCommitmentUpdate`. A counterparty can
+//! then verify a commitment with a CKB header, the root cell and a single
+//! proof, see `verify_commitment`.
+
+use alloc::vec::Vec;
+
+use sparse_merkle_tree::default_store::DefaultStore;
+use sparse_merkle_tree::traits::Hasher;
+use sparse_merkle_tree::{CompiledMerkleProof, SparseMerkleTree, H256};
+use tiny_keccak::{Hasher as _, Keccak};
+
+use crate::message::CommitmentKV;
+use crate::object::VerifyError;
+use crate::WriteOrVerifyCommitments;
+
+pub struct KeccakHasher(Keccak);
+
+impl Default for KeccakHasher {
+    fn default() -> Self {
+        Self(Keccak::v256())
+    }
+}
+
+impl Hasher for KeccakHasher {
+    fn write_h256(&mut self, h: &H256) {
+        self.0.update(h.as_slice());
+    }
+
+    fn write_byte(&mut self, b: u8) {
+        self.0.update(&[b]);
+    }
+
+    fn finish(self) -> H256 {
+        let mut output = [0u8; 32];
+        self.0.finalize(&mut output);
+        output.into()
+    }
+}
+
+fn leaf(kv: &CommitmentKV) -> (H256, H256) {
+    (kv.0 .0.into(), kv.1 .0.into())
+}
+
+/// Root from the data of the commitment root cell.
+pub fn root_from_cell_data(data: &[u8]) -> Result<[u8; 32], VerifyError> {
+    data.try_into().map_err(|_| VerifyError::WrongCellData)
+}
+
+fn verify_leaves(
+    root: [u8; 32],
+    proof: &[u8],
+    leaves: Vec<(H256, H256)>,
+) -> Result<(), VerifyError> {
+    match CompiledMerkleProof(proof.to_vec()).verify::<KeccakHasher>(&root.into(), leaves) {
+        Ok(true) => Ok(()),
+        _ => Err(VerifyError::Smt),
+    }
+}
+
+/// Verify that `path` has commitment `value` in the tree with `root`.
+pub fn verify_commitment(
+    root: [u8; 32],
+    proof: &[u8],
+    path: &[u8],
+    value: &[u8],
+) -> Result<(), VerifyError> {
+    verify_leaves(root, proof, vec![leaf(&CommitmentKV::hash(path, value))])
+}
+
+/// Verify an update of the commitment root cell.
+///
+/// `old_commitments` are the leaves before the update at the paths written
+/// by the handler, and `proof` proves them in the old tree. The same proof
+/// proves the written leaves in the new tree, because other leaves are not
+/// changed.
+pub struct SmtCommitmentUpdate<'a> {
+    pub old_root: [u8; 32],
+    pub new_root: [u8; 32],
+    pub old_commitments: &'a [CommitmentKV],
+    pub proof: &'a [u8],
+}
+
+impl SmtCommitmentUpdate<'_> {
+    fn verify(&self, new_commitments: &[CommitmentKV]) -> Result<(), VerifyError> {
+        let mut old_paths: Vec<_> = self.old_commitments.iter().map(|kv| kv.0).collect();
+        let mut new_paths: Vec<_> = new_commitments.iter().map(|kv| kv.0).collect();
+        old_paths.sort_unstable();
+        new_paths.sort_unstable();
+        if old_paths != new_paths {
+            return Err(VerifyError::Commitment);
+        }
+        if new_paths.windows(2).any(|w| matches!(w, [a, b] if a == b)) {
+            return Err(VerifyError::DuplicateCommitmentPath);
+        }
+        if new_commitments.is_empty() {
+            return if self.old_root == self.new_root {
+                Ok(())
+            } else {
+                Err(VerifyError::Smt)
+            };
+        }
+        let old_leaves = self.old_commitments.iter().map(leaf).collect();
+        verify_leaves(self.old_root, self.proof, old_leaves)?;
+        let new_leaves = new_commitments.iter().map(leaf).collect();
+        verify_leaves(self.new_root, self.proof, new_leaves)
+    }
+}
+
+impl WriteOrVerifyCommitments for SmtCommitmentUpdate<'_> {
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut new_commitments = Vec::new();
+        new_commitments.write_commitments(kvs)?;
+        self.verify(&new_commitments)
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut new_commitments = Vec::new();
+        new_commitments.delete_commitments(paths)?;
+        self.verify(&new_commitments)
+    }
+}
+
+/// The whole commitment tree, kept by relayers to build the witnesses of
+/// `SmtCommitmentUpdate` and proofs for `verify_commitment`.
+#[derive(Default)]
+pub struct CommitmentTree {
+    tree: SparseMerkleTree<KeccakHasher, H256, DefaultStore<H256>>,
+}
+
+impl CommitmentTree {
+    pub fn root(&self) -> [u8; 32] {
+        (*self.tree.root()).into()
+    }
+
+    /// Current leaves at `path_hashes`. Missing leaves have zero values.
+    pub fn commitments(&self, path_hashes: &[[u8; 32]]) -> Result<Vec<CommitmentKV>, VerifyError> {
+        path_hashes
+            .iter()
+            .map(|path_hash| {
+                let value: [u8; 32] = self
+                    .tree
+                    .get(&(*path_hash).into())
+                    .map_err(|_| VerifyError::Smt)?
+                    .into();
+                Ok(CommitmentKV((*path_hash).into(), value.into()))
+            })
+            .collect()
+    }
+
+    /// Compiled proof of leaves at `path_hashes`.
+    pub fn proof(&self, path_hashes: &[[u8; 32]]) -> Result<Vec<u8>, VerifyError> {
+        let keys: Vec<H256> = path_hashes.iter().map(|h| (*h).into()).collect();
+        let proof = self
+            .tree
+            .merkle_proof(keys.clone())
+            .and_then(|proof| proof.compile(keys))
+            .map_err(|_| VerifyError::Smt)?;
+        Ok(proof.0)
+    }
+
+    pub fn insert_commitments<'a>(
+        &mut self,
+        kvs: impl IntoIterator<Item = &'a CommitmentKV>,
+    ) -> Result<(), VerifyError> {
+        for kv in kvs {
+            let (key, value) = leaf(kv);
+            self.tree.update(key, value).map_err(|_| VerifyError::Smt)?;
+        }
+        Ok(())
+    }
+}
+
+impl WriteOrVerifyCommitments for CommitmentTree {
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut commitments = Vec::new();
+        commitments.write_commitments(kvs)?;
+        self.insert_commitments(&commitments)
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut commitments = Vec::new();
+        commitments.delete_commitments(paths)?;
+        self.insert_commitments(&commitments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_hashes(kvs: &[CommitmentKV]) -> Vec<[u8; 32]> {
+        kvs.iter().map(|kv| kv.0 .0).collect()
+    }
+
+    #[test]
+    fn test_verify_commitment() {
+        let mut tree = CommitmentTree::default();
+        tree.write_commitments([("a", "1"), ("b", "2")]).unwrap();
+        let kv = CommitmentKV::hash("a", "1");
+        let proof = tree.proof(&[kv.0 .0]).unwrap();
+        verify_commitment(tree.root(), &proof, b"a", b"1").unwrap();
+        assert!(matches!(
+            verify_commitment(tree.root(), &proof, b"a", b"2"),
+            Err(VerifyError::Smt)
+        ));
+
+        assert_eq!(root_from_cell_data(&tree.root()).unwrap(), tree.root());
+        assert!(root_from_cell_data(&[0; 31]).is_err());
+    }
+
+    #[test]
+    fn test_commitment_update() {
+        let mut tree = CommitmentTree::default();
+        tree.write_commitments([("a", "1"), ("b", "2")]).unwrap();
+        let old_root = tree.root();
+
+        // Update "b" and delete "a".
+        for (new, write) in [
+            (CommitmentKV::hash("b", "3"), true),
+            (CommitmentKV::deletion("a"), false),
+        ] {
+            let paths = path_hashes(core::slice::from_ref(&new));
+            let old_commitments = tree.commitments(&paths).unwrap();
+            let proof = tree.proof(&paths).unwrap();
+            let old_root = tree.root();
+            tree.insert_commitments([&new]).unwrap();
+            let mut update = SmtCommitmentUpdate {
+                old_root,
+                new_root: tree.root(),
+                old_commitments: &old_commitments,
+                proof: &proof,
+            };
+            if write {
+                update.write_commitments([("b", "3")]).unwrap();
+                assert!(update.write_commitments([("b", "4")]).is_err());
+                assert!(update.write_commitments([("c", "3")]).is_err());
+            } else {
+                update.delete_commitments(["a"]).unwrap();
+                assert!(update.delete_commitments(["b"]).is_err());
+            }
+        }
+        assert_ne!(tree.root(), old_root);
+
+        // No commitments, no change.
+        let root = tree.root();
+        let mut update = SmtCommitmentUpdate {
+            old_root: root,
+            new_root: root,
+            old_commitments: &[],
+            proof: &[],
+        };
+        update.write_no_commitment().unwrap();
+        update.new_root = old_root;
+        assert!(update.write_no_commitment().is_err());
+    }
+}
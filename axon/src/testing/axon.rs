@@ -0,0 +1,494 @@
+//! Deterministic in-process Axon chain.
+//!
+//! Validators are generated from fixed seeds, so the metadata cell data and
+//! block proofs are the same in every run. Every block is signed by all
+//! validators, the same way Axon does it, so `AxonClient` verifies them with
+//! real BLS signatures.
+
+use std::vec::Vec;
+
+use axon_tools::types::{
+    Block as AxonBlock, Header as AxonHeader, Proof as AxonBlockProof, Proposal, Vote,
+};
+use axon_types::metadata::{Metadata, MetadataCellData, MetadataList, Validator, ValidatorList};
+use blst::min_pk::{AggregateSignature, SecretKey};
+use ethereum_types::{H160, H256};
+use molecule::prelude::*;
+
+use super::BlockSigner;
+use crate::axon_client::{keccak256, AxonClient};
+use crate::object::VerifyError;
+use crate::proto::client::Height;
+
+/// Domain separation tag of BLS signatures used by Axon.
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Vote type of precommit votes, which make up block proofs.
+const PRECOMMIT: u8 = 2;
+
+const BLOCK_INTERVAL_MS: u64 = 3000;
+
+/// Mock Axon chain. Each call to `sign_block` produces the next block.
+pub struct MockAxonChain {
+    secret_keys: Vec<SecretKey>,
+    /// Number of the latest block.
+    pub number: u64,
+    /// Hash of the latest block.
+    pub block_hash: H256,
+    /// State root of the latest block.
+    pub state_root: H256,
+}
+
+impl MockAxonChain {
+    /// Create a chain with `validator_count` validators, which must not be
+    /// zero.
+    pub fn new(validator_count: u8) -> Self {
+        assert!(validator_count > 0, "at least one validator is needed");
+        let secret_keys = (1..=validator_count)
+            .map(|i| SecretKey::key_gen(&[i; 32], &[]).unwrap())
+            .collect();
+        Self {
+            secret_keys,
+            number: 0,
+            block_hash: H256::zero(),
+            state_root: H256::zero(),
+        }
+    }
+
+    pub fn validator_count(&self) -> usize {
+        self.secret_keys.len()
+    }
+
+    /// Metadata cell data with the validators of this chain, for
+    /// `AxonClient::new`.
+    pub fn metadata_cell_data(&self) -> Vec<u8> {
+        let validators = self
+            .secret_keys
+            .iter()
+            .zip(1u8..)
+            .map(|(sk, i)| {
+                Validator::new_builder()
+                    .bls_pub_key(entity(&sk.sk_to_pk().compress()))
+                    // Not used in block proofs. Only has to be unique.
+                    .pub_key(entity(&[[2].as_slice(), &[i; 32]].concat()))
+                    .address(entity(&[i; 20]))
+                    .propose_weight(entity(&1u32.to_le_bytes()))
+                    .vote_weight(entity(&1u32.to_le_bytes()))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let metadata = Metadata::new_builder()
+            .validators(ValidatorList::new_builder().set(validators).build())
+            .build();
+        MetadataCellData::new_builder()
+            .metadata(MetadataList::new_builder().push(metadata).build())
+            .build()
+            .as_slice()
+            .to_vec()
+    }
+
+    /// Light client of this chain that verifies commitments of the IBC
+    /// handler at `ibc_handler_address`. It trusts every block, including
+    /// the ones signed after it's created.
+    pub fn client(&self, ibc_handler_address: [u8; 20]) -> Result<AxonClient, VerifyError> {
+        AxonClient::new(
+            ibc_handler_address,
+            &self.metadata_cell_data(),
+            Height::new(0, u64::MAX),
+        )
+    }
+
+    fn sign(&self, number: u64, block_hash: H256) -> AxonBlockProof {
+        let vote = Vote {
+            height: number,
+            round: 0,
+            vote_type: PRECOMMIT,
+            block_hash: block_hash.as_bytes().to_vec().into(),
+        };
+        let vote_hash = keccak256(&rlp::encode(&vote));
+        let signatures = self
+            .secret_keys
+            .iter()
+            .map(|sk| sk.sign(&vote_hash, BLS_DST, &[]))
+            .collect::<Vec<_>>();
+        let signature = AggregateSignature::aggregate(&signatures.iter().collect::<Vec<_>>(), true)
+            .unwrap()
+            .to_signature();
+        // Every validator signs.
+        let bitmap = signer_bitmap(self.secret_keys.len());
+
+        AxonBlockProof {
+            number,
+            round: 0,
+            block_hash,
+            signature: signature.compress().to_vec().into(),
+            bitmap: bitmap.into(),
+        }
+    }
+}
+
+impl BlockSigner for MockAxonChain {
+    fn sign_block(&mut self, state_root: H256) -> (AxonBlock, H256, AxonBlockProof) {
+        let number = self.number + 1;
+        let block = AxonBlock {
+            header: AxonHeader {
+                prev_hash: self.block_hash,
+                proposer: H160::repeat_byte(1),
+                state_root,
+                timestamp: number * BLOCK_INTERVAL_MS,
+                number,
+                ..Default::default()
+            },
+            tx_hashes: Vec::new(),
+        };
+        let previous_state_root = self.state_root;
+
+        let proposal = Proposal::new_with_state_root(
+            &block.header,
+            previous_state_root,
+            block.tx_hashes.clone(),
+        );
+        let block_hash = H256::from(keccak256(&rlp::encode(&proposal)));
+        let proof = self.sign(number, block_hash);
+
+        self.number = number;
+        self.block_hash = block_hash;
+        self.state_root = state_root;
+
+        (block, previous_state_root, proof)
+    }
+}
+
+/// Bitmap of block proof signers with the first `n` validators set, most
+/// significant bit first. Bits past the last validator are left unset.
+fn signer_bitmap(n: usize) -> Vec<u8> {
+    let mut bitmap = vec![0; (n + 7) / 8];
+    for i in 0..n {
+        bitmap[i / 8] |= 0x80 >> (i % 8);
+    }
+    bitmap
+}
+
+fn entity<T: Entity>(data: &[u8]) -> T {
+    T::new_unchecked(data.to_vec().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::connection_path;
+    use crate::handler::*;
+    use crate::message::{MsgConnectionOpenAck, MsgConnectionOpenConfirm, MsgConnectionOpenTry};
+    use crate::object::{ConnectionCounterparty, ConnectionEnd, State};
+    use crate::testing::{IbcHandlerState, SelfClientStates};
+    use crate::{connection_id, ConnectionArgs, WriteOrVerifyCommitments};
+
+    #[test]
+    fn test_deterministic() {
+        let mut a = MockAxonChain::new(4);
+        let mut b = MockAxonChain::new(4);
+        assert_eq!(a.metadata_cell_data(), b.metadata_cell_data());
+        let root = H256::repeat_byte(3);
+        assert_eq!(
+            rlp::encode(&a.sign_block(root).2),
+            rlp::encode(&b.sign_block(root).2)
+        );
+    }
+
+    #[test]
+    fn test_signer_bitmap() {
+        assert_eq!(signer_bitmap(3), [0b1110_0000]);
+        assert_eq!(signer_bitmap(8), [0xff]);
+        assert_eq!(signer_bitmap(9), [0xff, 0b1000_0000]);
+
+        // A chain whose validator count isn't a multiple of 8.
+        let mut chain = MockAxonChain::new(5);
+        let mut state = IbcHandlerState::new([2; 20]);
+        state.write_commitments([("a", "1")]).unwrap();
+        let client = chain.client([2; 20]).unwrap();
+        let (height, proof) = state.axon_proof(&mut chain, "a");
+        client
+            .verify_membership(height, &proof, b"a", b"1")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let mut chain = MockAxonChain::new(4);
+        let mut state = IbcHandlerState::new([2; 20]);
+        state.write_commitments([("a", "1"), ("b", "2")]).unwrap();
+        let client = chain.client([2; 20]).unwrap();
+
+        let (height, proof) = state.axon_proof(&mut chain, "a");
+        client
+            .verify_membership(height, &proof, b"a", b"1")
+            .unwrap();
+        assert!(client
+            .verify_membership(height, &proof, b"a", b"2")
+            .is_err());
+
+        // Later blocks are verified as well.
+        let (height, proof) = state.axon_batch_proof(&mut chain, &["a", "b"]);
+        assert_eq!(height.revision_height, 2);
+        let kvs: [(&[u8], &[u8]); 2] = [(b"a", b"1"), (b"b", b"2")];
+        client
+            .verify_membership_batch(height, &proof, &kvs)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_metadata_cell_identity() {
+        let mut chain = MockAxonChain::new(4);
+        let mut state = IbcHandlerState::new([2; 20]);
+        state.write_commitments([("a", "1")]).unwrap();
+        let (height, proof) = state.axon_proof(&mut chain, "a");
+        let args = ConnectionArgs {
+            metadata_type_id: [5; 32],
+            ibc_handler_address: [2; 20],
+        };
+        let data = chain.metadata_cell_data();
+
+        let client = AxonClient::from_metadata_cell(&args, Some([5; 32]), &data, height).unwrap();
+        client
+            .verify_membership(height, &proof, b"a", b"1")
+            .unwrap();
+        // A forged metadata cell of another type, or without a type script.
+        for type_hash in [Some([6; 32]), None] {
+            assert!(matches!(
+                AxonClient::from_metadata_cell(&args, type_hash, &data, height),
+                Err(VerifyError::WrongMetadataCell)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_proof_height() {
+        let mut chain = MockAxonChain::new(4);
+        let mut state = IbcHandlerState::new([2; 20]);
+        state.write_commitments([("a", "1")]).unwrap();
+        let (height, proof) = state.axon_proof(&mut chain, "a");
+        let data = chain.metadata_cell_data();
+
+        let client = AxonClient::new([2; 20], &data, height).unwrap();
+        assert_eq!(client.latest_height(), Some(height));
+        client
+            .verify_membership(height, &proof, b"a", b"1")
+            .unwrap();
+
+        // The block is after the latest height of the client.
+        let (later, proof) = state.axon_proof(&mut chain, "a");
+        assert!(matches!(
+            client.verify_membership(later, &proof, b"a", b"1"),
+            Err(VerifyError::InvalidProofHeight)
+        ));
+
+        // Axon has no revisions, so proofs on other revisions are rejected.
+        let client = AxonClient::new([2; 20], &data, later).unwrap();
+        let other_revision = Height::new(1, later.revision_height);
+        assert!(matches!(
+            client.verify_membership(other_revision, &proof, b"a", b"1"),
+            Err(VerifyError::InvalidProofHeight)
+        ));
+        let client = AxonClient::new([2; 20], &data, other_revision).unwrap();
+        assert!(matches!(
+            client.verify_membership(later, &proof, b"a", b"1"),
+            Err(VerifyError::InvalidProofHeight)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_validators() {
+        let mut chain = MockAxonChain::new(4);
+        let mut state = IbcHandlerState::new([2; 20]);
+        state.write_commitments([("a", "1")]).unwrap();
+        let (height, proof) = state.axon_proof(&mut chain, "a");
+
+        let client = MockAxonChain::new(3).client([2; 20]).unwrap();
+        assert!(matches!(
+            client.verify_membership(height, &proof, b"a", b"1"),
+            Err(VerifyError::InvalidReceiptProof)
+        ));
+    }
+
+    /// A chain with its IBC handler state and light client of the
+    /// counterparty.
+    struct Chain {
+        args: ConnectionArgs,
+        axon: MockAxonChain,
+        state: IbcHandlerState,
+        connections: IbcConnections,
+        /// Client of this chain that the counterparty should have.
+        self_client: SelfClientStates,
+    }
+
+    impl Chain {
+        fn new(seed: u8) -> Self {
+            let args = ConnectionArgs {
+                metadata_type_id: [seed; 32],
+                ibc_handler_address: [seed; 20],
+            };
+            Self {
+                args,
+                axon: MockAxonChain::new(4),
+                state: IbcHandlerState::new(args.ibc_handler_address),
+                connections: IbcConnections::default(),
+                self_client: SelfClientStates {
+                    client_state: vec![seed],
+                    consensus_height: Height::new(0, 1),
+                    consensus_state: vec![seed, 1],
+                },
+            }
+        }
+
+        /// Create the client of `counterparty` on this chain.
+        fn create_client(&mut self, counterparty: &SelfClientStates) {
+            self.state
+                .write_commitments(counterparty.commitments(&self.args.client_id()))
+                .unwrap();
+        }
+
+        /// Proofs of a connection and of the client of `counterparty` on this
+        /// chain, in the same block.
+        fn handshake_proofs(
+            &mut self,
+            connection_id: &str,
+            counterparty: &SelfClientStates,
+        ) -> (Height, Vec<Vec<u8>>) {
+            let [client_path, consensus_path] = counterparty.paths(&self.args.client_id());
+            self.state.axon_proofs(
+                &mut self.axon,
+                &[connection_path(connection_id), client_path, consensus_path],
+            )
+        }
+
+        fn proof(&mut self, connection_id: &str) -> (Height, Vec<u8>) {
+            self.state
+                .axon_proof(&mut self.axon, connection_path(connection_id))
+        }
+    }
+
+    #[test]
+    fn test_connection_handshake() {
+        let mut a = Chain::new(1);
+        let mut b = Chain::new(2);
+        let client_of_a = a.axon.client(a.args.ibc_handler_address).unwrap();
+        let client_of_b = b.axon.client(b.args.ibc_handler_address).unwrap();
+        a.create_client(&b.self_client);
+        b.create_client(&a.self_client);
+
+        // Init on A.
+        let mut new = a.connections.clone();
+        new.connections.push(ConnectionEnd {
+            state: State::Init,
+            counterparty: ConnectionCounterparty {
+                client_id: b.args.client_id(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        handle_msg_connection_open_init(
+            a.connections.clone(),
+            a.args,
+            new.clone(),
+            a.args,
+            &mut a.state,
+        )
+        .unwrap();
+        a.connections = new;
+        let a_connection_id = connection_id(&a.args.client_id(), 0);
+
+        // Try on B.
+        let (proof_height, proofs) = a.handshake_proofs(&a_connection_id, &b.self_client);
+        let [proof_init, proof_client, proof_consensus] = <[_; 3]>::try_from(proofs).unwrap();
+        let mut new = b.connections.clone();
+        new.connections.push(ConnectionEnd {
+            state: State::OpenTry,
+            counterparty: ConnectionCounterparty {
+                client_id: a.args.client_id(),
+                connection_id: a_connection_id.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        handle_msg_connection_open_try(
+            &client_of_a,
+            &b.self_client,
+            b.connections.clone(),
+            b.args,
+            new.clone(),
+            b.args,
+            &mut b.state,
+            MsgConnectionOpenTry {
+                proof_height,
+                proof_init,
+                client_state_of_b_on_a: b.self_client.client_state.clone(),
+                proof_client_state_of_b_on_a: proof_client,
+                consensus_height_of_b_on_a: b.self_client.consensus_height,
+                consensus_state_of_b_on_a: b.self_client.consensus_state.clone(),
+                proof_consensus_state_of_b_on_a: proof_consensus,
+            },
+        )
+        .unwrap();
+        b.connections = new;
+        let b_connection_id = connection_id(&b.args.client_id(), 0);
+
+        // Ack on A. A proof of B's try verified by a client of A fails.
+        let (proof_height, proofs) = b.handshake_proofs(&b_connection_id, &a.self_client);
+        let [proof_try, proof_client, proof_consensus] = <[_; 3]>::try_from(proofs).unwrap();
+        let mut new = a.connections.clone();
+        new.connections[0].state = State::Open;
+        new.connections[0].counterparty.connection_id = b_connection_id.clone();
+        let msg = || MsgConnectionOpenAck {
+            conn_id_on_a: 0,
+            proof_height,
+            proof_try: proof_try.clone(),
+            client_state_of_a_on_b: a.self_client.client_state.clone(),
+            proof_client_state_of_a_on_b: proof_client.clone(),
+            consensus_height_of_a_on_b: a.self_client.consensus_height,
+            consensus_state_of_a_on_b: a.self_client.consensus_state.clone(),
+            proof_consensus_state_of_a_on_b: proof_consensus.clone(),
+        };
+        handle_msg_connection_open_ack(
+            &client_of_a,
+            &a.self_client,
+            a.connections.clone(),
+            a.args,
+            new.clone(),
+            a.args,
+            &mut Vec::new(),
+            msg(),
+        )
+        .unwrap_err();
+        handle_msg_connection_open_ack(
+            &client_of_b,
+            &a.self_client,
+            a.connections.clone(),
+            a.args,
+            new.clone(),
+            a.args,
+            &mut a.state,
+            msg(),
+        )
+        .unwrap();
+        a.connections = new;
+
+        // Confirm on B.
+        let (proof_height, proof_ack) = a.proof(&a_connection_id);
+        let mut new = b.connections.clone();
+        new.connections[0].state = State::Open;
+        handle_msg_connection_open_confirm(
+            &client_of_a,
+            b.connections.clone(),
+            b.args,
+            new,
+            b.args,
+            &mut b.state,
+            MsgConnectionOpenConfirm {
+                conn_id_on_b: 0,
+                proof_height,
+                proof_ack,
+            },
+        )
+        .unwrap();
+    }
+}
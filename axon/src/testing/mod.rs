@@ -0,0 +1,537 @@
+//! Utilities for building commitment proofs in tests and relayers.
+//!
+//! `IbcHandlerState` keeps an in-memory EVM state with only the IBC handler
+//! contract, whose storage holds the commitments. It emits account and
+//! storage proofs for commitment paths, which can be combined with a signed
+//! block from a `BlockSigner` to get a complete `AxonCommitmentProof`.
+//! `MockAxonChain` is a `BlockSigner` with real BLS signatures, which
+//! `AxonClient` verifies.
+//!
+//! `sim::Simulator` runs two chains connected by IBC through the handlers.
+
+use std::sync::Arc;
+use std::vec::Vec;
+
+use axon_tools::types::{Block as AxonBlock, Proof as AxonBlockProof};
+use cita_trie::{MemoryDB, PatriciaTrie, Trie};
+use ethereum_types::{H256, U256};
+use hasher::HasherKeccak;
+use rlp::RlpStream;
+
+use crate::axon_client::slot::CommitmentSlotLayout;
+use crate::axon_client::{keccak256, AxonCommitmentBatchProof, AxonCommitmentProof, ProofNode};
+use crate::commitment::{client_state_path, consensus_state_path};
+use crate::handler::ValidateSelfClient;
+use crate::message::CommitmentKV;
+use crate::object::VerifyError;
+use crate::proto::client::Height;
+use crate::WriteOrVerifyCommitments;
+
+mod axon;
+pub mod sim;
+
+pub use axon::MockAxonChain;
+
+type KeccakTrie = PatriciaTrie<MemoryDB, HasherKeccak>;
+
+fn new_trie() -> KeccakTrie {
+    PatriciaTrie::new(
+        Arc::new(MemoryDB::new(false)),
+        Arc::new(HasherKeccak::new()),
+    )
+}
+
+/// Produces signed blocks for commitment proofs, e.g. a mock Axon chain.
+pub trait BlockSigner {
+    /// Produce a block with `state_root`. Returns the block, state root of the
+    /// previous block and proof of the block.
+    fn sign_block(&mut self, state_root: H256) -> (AxonBlock, H256, AxonBlockProof);
+}
+
+/// RLP encoded London block header of an EVM chain, for `EthClient`. Fields
+/// other than the parent hash, state root and number are fixed.
+pub fn eth_header(parent_hash: H256, state_root: H256, number: u64) -> Vec<u8> {
+    let mut s = RlpStream::new_list(15);
+    s.append(&parent_hash);
+    s.append(&H256::zero());
+    s.append(&[0u8; 20].as_slice());
+    s.append(&state_root);
+    s.append(&H256::zero());
+    s.append(&H256::zero());
+    s.append(&[0u8; 256].as_slice());
+    s.append(&0u64);
+    s.append(&number);
+    s.append(&30_000_000u64);
+    s.append(&0u64);
+    s.append(&1_700_000_000u64);
+    s.append_empty_data();
+    s.append(&H256::zero());
+    s.append(&[0u8; 8].as_slice());
+    s.out().to_vec()
+}
+
+/// Account and storage proofs of commitments.
+pub struct CommitmentProof {
+    pub state_root: H256,
+    pub account_proof: Vec<ProofNode>,
+    /// Deduplicated storage proof nodes of all proven paths.
+    pub storage_proof: Vec<ProofNode>,
+}
+
+/// In-memory EVM state with an IBC handler contract.
+pub struct IbcHandlerState {
+    pub ibc_handler_address: [u8; 20],
+    pub slot_layout: CommitmentSlotLayout,
+    storage: KeccakTrie,
+}
+
+impl IbcHandlerState {
+    pub fn new(ibc_handler_address: [u8; 20]) -> Self {
+        Self {
+            ibc_handler_address,
+            slot_layout: CommitmentSlotLayout::default(),
+            storage: new_trie(),
+        }
+    }
+
+    pub fn with_slot_layout(mut self, slot_layout: CommitmentSlotLayout) -> Self {
+        self.slot_layout = slot_layout;
+        self
+    }
+
+    /// Write commitments as the IBC handler contract would.
+    pub fn insert_commitments<'a>(&mut self, kvs: impl IntoIterator<Item = &'a CommitmentKV>) {
+        for CommitmentKV(path_hash, value_hash) in kvs {
+            let slot = self.slot_layout.slot_of_hashed_path(path_hash.0);
+            let key = keccak256(&slot).to_vec();
+            if value_hash.is_zero() {
+                self.storage.remove(&key).unwrap();
+            } else {
+                let value = rlp::encode(&U256::from(value_hash.as_bytes())).to_vec();
+                self.storage.insert(key, value).unwrap();
+            }
+        }
+    }
+
+    /// Storage root of the IBC handler contract.
+    pub fn storage_root(&mut self) -> H256 {
+        H256::from_slice(&self.storage.root().unwrap())
+    }
+
+    fn account_trie(&mut self) -> KeccakTrie {
+        let mut account = RlpStream::new_list(4);
+        // nonce, balance, storage_root, code_hash
+        account.append(&0u64);
+        account.append(&U256::zero());
+        account.append(&self.storage_root());
+        account.append(&H256::from(keccak256(&[])));
+
+        let mut accounts = new_trie();
+        accounts
+            .insert(
+                keccak256(&self.ibc_handler_address).to_vec(),
+                account.out().to_vec(),
+            )
+            .unwrap();
+        accounts
+    }
+
+    /// State root of the EVM state.
+    pub fn state_root(&mut self) -> H256 {
+        H256::from_slice(&self.account_trie().root().unwrap())
+    }
+
+    /// Account proof of the IBC handler and storage proof of `paths`.
+    pub fn proof<P: AsRef<[u8]>>(&mut self, paths: &[P]) -> CommitmentProof {
+        let mut accounts = self.account_trie();
+        let state_root = H256::from_slice(&accounts.root().unwrap());
+        let account_proof = accounts
+            .get_proof(&keccak256(&self.ibc_handler_address))
+            .unwrap();
+
+        let mut storage_proof: Vec<ProofNode> = Vec::new();
+        for path in paths {
+            let slot = self.slot_layout.slot(path.as_ref());
+            for node in self.storage.get_proof(&keccak256(&slot)).unwrap() {
+                if !storage_proof.contains(&node) {
+                    storage_proof.push(node);
+                }
+            }
+        }
+
+        CommitmentProof {
+            state_root,
+            account_proof,
+            storage_proof,
+        }
+    }
+
+    /// Build an encoded `AxonCommitmentProof` of `path`, in a block signed by
+    /// `signer`.
+    pub fn axon_proof(
+        &mut self,
+        signer: &mut impl BlockSigner,
+        path: impl AsRef<[u8]>,
+    ) -> (Height, Vec<u8>) {
+        let (height, mut proofs) = self.axon_proofs(signer, &[path]);
+        (height, proofs.remove(0))
+    }
+
+    /// Build an encoded `AxonCommitmentProof` of each of `paths`, all in the
+    /// same block signed by `signer`.
+    pub fn axon_proofs<P: AsRef<[u8]>>(
+        &mut self,
+        signer: &mut impl BlockSigner,
+        paths: &[P],
+    ) -> (Height, Vec<Vec<u8>>) {
+        let state_root = self.state_root();
+        let (block, previous_state_root, block_proof) = signer.sign_block(state_root);
+        let height = block_height(&block);
+        let proofs = paths
+            .iter()
+            .map(|path| {
+                let CommitmentProof {
+                    account_proof,
+                    storage_proof,
+                    ..
+                } = self.proof(&[path]);
+                let proof = AxonCommitmentProof {
+                    block: block.clone(),
+                    previous_state_root,
+                    block_proof: block_proof.clone(),
+                    account_proof,
+                    storage_proof,
+                };
+                rlp::encode(&proof).to_vec()
+            })
+            .collect();
+        (height, proofs)
+    }
+
+    /// Build an encoded `AxonCommitmentBatchProof` of `paths`, in a block
+    /// signed by `signer`.
+    pub fn axon_batch_proof<P: AsRef<[u8]>>(
+        &mut self,
+        signer: &mut impl BlockSigner,
+        paths: &[P],
+    ) -> (Height, Vec<u8>) {
+        let CommitmentProof {
+            state_root,
+            account_proof,
+            storage_proof,
+        } = self.proof(paths);
+        let (block, previous_state_root, block_proof) = signer.sign_block(state_root);
+        let height = block_height(&block);
+        let proof = AxonCommitmentBatchProof {
+            block,
+            previous_state_root,
+            block_proof,
+            account_proof,
+            storage_proof,
+        };
+        (height, rlp::encode(&proof).to_vec())
+    }
+}
+
+/// Client and consensus states of a chain, as the counterparty's client of
+/// the chain should have them. Checks them in the connection handshake as
+/// `ValidateSelfClient`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelfClientStates {
+    pub client_state: Vec<u8>,
+    pub consensus_height: Height,
+    pub consensus_state: Vec<u8>,
+}
+
+impl SelfClientStates {
+    /// Commitments of these states in a client with `client_id` on the
+    /// counterparty chain.
+    pub fn commitments(&self, client_id: &str) -> [(String, Vec<u8>); 2] {
+        [
+            (client_state_path(client_id), self.client_state.clone()),
+            (
+                consensus_state_path(client_id, self.consensus_height),
+                self.consensus_state.clone(),
+            ),
+        ]
+    }
+
+    /// Paths of `commitments`.
+    pub fn paths(&self, client_id: &str) -> [String; 2] {
+        self.commitments(client_id).map(|(path, _)| path)
+    }
+}
+
+impl ValidateSelfClient for SelfClientStates {
+    fn validate_self_client(&self, client_state: &[u8]) -> Result<(), VerifyError> {
+        if client_state == self.client_state {
+            Ok(())
+        } else {
+            Err(VerifyError::InvalidSelfClient)
+        }
+    }
+
+    fn validate_self_consensus_state(
+        &self,
+        height: Height,
+        consensus_state: &[u8],
+    ) -> Result<(), VerifyError> {
+        if height == self.consensus_height && consensus_state == self.consensus_state {
+            Ok(())
+        } else {
+            Err(VerifyError::InvalidSelfClient)
+        }
+    }
+}
+
+fn block_height(block: &AxonBlock) -> Height {
+    Height {
+        revision_number: 0,
+        revision_height: block.header.number,
+    }
+}
+
+// Handlers can write commitments to the state directly.
+impl WriteOrVerifyCommitments for IbcHandlerState {
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let kvs: Vec<CommitmentKV> = kvs
+            .into_iter()
+            .map(|(k, v)| CommitmentKV::hash(k, v))
+            .collect();
+        self.insert_commitments(&kvs);
+        Ok(())
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let kvs: Vec<CommitmentKV> = paths.into_iter().map(CommitmentKV::deletion).collect();
+        self.insert_commitments(&kvs);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axon_client::verify::{verify_account, verify_account_and_storage};
+    use crate::axon_client::verify::{verify_storage, verify_storage_multiproof};
+    use crate::commitment::connection_path;
+    use crate::eth_client::{EthClient, EthCommitmentProof, TrustedHeader};
+    use crate::handler::*;
+    use crate::message::MsgConnectionOpenTry;
+    use crate::object::{ConnectionCounterparty, ConnectionEnd, State};
+    use crate::{connection_id, ConnectionArgs};
+
+    #[test]
+    fn test_commitment_proof() {
+        let mut state = IbcHandlerState::new([5; 20]);
+        state
+            .write_commitments([("a", "1"), ("b", "2"), ("c", "3")])
+            .unwrap();
+
+        let CommitmentProof {
+            state_root,
+            account_proof,
+            storage_proof,
+        } = state.proof(&["a"]);
+        verify_account_and_storage(
+            state_root.as_bytes(),
+            &[5; 20],
+            &account_proof,
+            state.slot_layout.slot(b"a"),
+            keccak256(b"1"),
+            &storage_proof,
+        )
+        .unwrap();
+        // Non-existent path.
+        let CommitmentProof { storage_proof, .. } = state.proof(&["d"]);
+        let storage_root = verify_account(state_root.as_bytes(), &[5; 20], &account_proof).unwrap();
+        verify_storage(
+            storage_root,
+            state.slot_layout.slot(b"d"),
+            [0; 32],
+            &storage_proof,
+        )
+        .unwrap();
+
+        let CommitmentProof { storage_proof, .. } = state.proof(&["a", "c"]);
+        verify_storage_multiproof(
+            storage_root,
+            [
+                (state.slot_layout.slot(b"a"), keccak256(b"1")),
+                (state.slot_layout.slot(b"c"), keccak256(b"3")),
+            ],
+            &storage_proof,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_connection_open_try_with_eth_client() {
+        let a_args = ConnectionArgs {
+            metadata_type_id: [1; 32],
+            ibc_handler_address: [2; 20],
+        };
+        let b_args = ConnectionArgs {
+            metadata_type_id: [3; 32],
+            ibc_handler_address: [4; 20],
+        };
+
+        // Chain A is an EVM chain, so its commitments are stored in its IBC
+        // handler contract.
+        let mut a_state = IbcHandlerState::new(a_args.ibc_handler_address);
+        let a_connections = IbcConnections {
+            connections: vec![ConnectionEnd {
+                state: State::Init,
+                counterparty: ConnectionCounterparty {
+                    client_id: b_args.client_id(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        handle_msg_connection_open_init(
+            IbcConnections::default(),
+            a_args,
+            a_connections,
+            a_args,
+            &mut a_state,
+        )
+        .unwrap();
+
+        // A's client of B.
+        let b_states = SelfClientStates {
+            client_state: b"client of b".to_vec(),
+            consensus_height: Height {
+                revision_number: 0,
+                revision_height: 1,
+            },
+            consensus_state: b"consensus of b".to_vec(),
+        };
+        a_state
+            .write_commitments(b_states.commitments(&a_args.client_id()))
+            .unwrap();
+
+        let a_connection_id = connection_id(&a_args.client_id(), 0);
+        let state_root = a_state.state_root();
+        let header = eth_header(H256::zero(), state_root, 10);
+        let client = EthClient::new(
+            a_args.ibc_handler_address,
+            vec![TrustedHeader {
+                number: 10,
+                hash: keccak256(&header).into(),
+            }],
+        )
+        .unwrap();
+        let mut eth_proof = |path: &str| {
+            let CommitmentProof {
+                account_proof,
+                storage_proof,
+                ..
+            } = a_state.proof(&[path]);
+            rlp::encode(&EthCommitmentProof {
+                header: header.clone(),
+                account_proof,
+                storage_proof,
+            })
+            .to_vec()
+        };
+        let proof_init = eth_proof(&connection_path(&a_connection_id));
+        let [client_path, consensus_path] = b_states.paths(&a_args.client_id());
+        let proof_client = eth_proof(&client_path);
+        let proof_consensus = eth_proof(&consensus_path);
+
+        let b_connections = IbcConnections {
+            connections: vec![ConnectionEnd {
+                state: State::OpenTry,
+                counterparty: ConnectionCounterparty {
+                    client_id: a_args.client_id(),
+                    connection_id: a_connection_id,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let msg = |revision_height| MsgConnectionOpenTry {
+            proof_height: Height {
+                revision_number: 0,
+                revision_height,
+            },
+            proof_init: proof_init.clone(),
+            client_state_of_b_on_a: b_states.client_state.clone(),
+            proof_client_state_of_b_on_a: proof_client.clone(),
+            consensus_height_of_b_on_a: b_states.consensus_height,
+            consensus_state_of_b_on_a: b_states.consensus_state.clone(),
+            proof_consensus_state_of_b_on_a: proof_consensus.clone(),
+        };
+        handle_msg_connection_open_try(
+            &client,
+            &b_states,
+            IbcConnections::default(),
+            b_args,
+            b_connections.clone(),
+            b_args,
+            &mut Vec::new(),
+            msg(10),
+        )
+        .unwrap();
+        // Wrong proof height.
+        handle_msg_connection_open_try(
+            &client,
+            &b_states,
+            IbcConnections::default(),
+            b_args,
+            b_connections.clone(),
+            b_args,
+            &mut Vec::new(),
+            msg(11),
+        )
+        .unwrap_err();
+        // A's client of B isn't set up as B expects.
+        let r = handle_msg_connection_open_try(
+            &client,
+            SelfClientStates {
+                client_state: b"other client of b".to_vec(),
+                ..b_states.clone()
+            },
+            IbcConnections::default(),
+            b_args,
+            b_connections.clone(),
+            b_args,
+            &mut Vec::new(),
+            msg(10),
+        );
+        assert!(matches!(r, Err(VerifyError::InvalidSelfClient)));
+        // Consensus state that A doesn't have.
+        let mut wrong_consensus = msg(10);
+        wrong_consensus.consensus_height_of_b_on_a.revision_height = 2;
+        let r = handle_msg_connection_open_try(
+            &client,
+            SelfClientStates {
+                consensus_height: wrong_consensus.consensus_height_of_b_on_a,
+                ..b_states.clone()
+            },
+            IbcConnections::default(),
+            b_args,
+            b_connections,
+            b_args,
+            &mut Vec::new(),
+            wrong_consensus,
+        );
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,1172 @@
+//! In-memory simulation of two chains connected by IBC.
+//!
+//! Each chain holds its IBC cells, a `Backend` that stores the commitments it
+//! writes and proves them, and a light client of the counterparty. Steps of
+//! `Simulator` build the next cells the way a relayer would and run them
+//! through the handlers, so cells are only updated if the handler accepts
+//! them. Proofs of the next step can be broken with a `Fault` to test
+//! failures.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use ethereum_types::H256;
+
+use super::{IbcHandlerState, MockAxonChain, SelfClientStates};
+use crate::axon_client::{keccak256, AxonClient};
+use crate::commitment::*;
+use crate::handler::*;
+use crate::message::*;
+use crate::object::{
+    ChannelCounterparty, ConnectionCounterparty, ConnectionEnd, Ordering, Packet, State,
+    VerifyError,
+};
+use crate::proto::client::Height;
+use crate::{connection_id, ChannelArgs, ConnectionArgs, PacketArgs, WriteOrVerifyCommitments};
+
+/// Commitment storage of a chain.
+pub trait Backend: WriteOrVerifyCommitments {
+    /// Light client that verifies proofs of this chain.
+    type Client: Client;
+
+    /// Prove commitments at `paths` in the current state, all at the same
+    /// height. Returns the proof height and a proof for each path.
+    fn prove_paths(&mut self, paths: &[&str]) -> (Height, Vec<Vec<u8>>);
+
+    fn client(&self) -> Self::Client;
+}
+
+/// Commitments kept in memory. Proofs are digests of snapshots of all
+/// commitments, one snapshot for each height.
+#[derive(Default)]
+pub struct MemoryBackend {
+    pub commitments: BTreeMap<H256, H256>,
+    snapshots: Rc<RefCell<Vec<BTreeMap<H256, H256>>>>,
+}
+
+pub struct MemoryClient {
+    snapshots: Rc<RefCell<Vec<BTreeMap<H256, H256>>>>,
+}
+
+fn digest(commitments: &BTreeMap<H256, H256>) -> [u8; 32] {
+    let data: Vec<u8> = commitments
+        .iter()
+        .flat_map(|(k, v)| [k.as_bytes(), v.as_bytes()].concat())
+        .collect();
+    keccak256(&data)
+}
+
+impl WriteOrVerifyCommitments for MemoryBackend {
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (k, v) in kvs {
+            self.apply(CommitmentKV::hash(k, v));
+        }
+        Ok(())
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        for path in paths {
+            self.apply(CommitmentKV::deletion(path));
+        }
+        Ok(())
+    }
+}
+
+impl MemoryBackend {
+    fn apply(&mut self, CommitmentKV(path_hash, value_hash): CommitmentKV) {
+        if value_hash.is_zero() {
+            self.commitments.remove(&path_hash);
+        } else {
+            self.commitments.insert(path_hash, value_hash);
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    type Client = MemoryClient;
+
+    fn prove_paths(&mut self, paths: &[&str]) -> (Height, Vec<Vec<u8>>) {
+        let mut snapshots = self.snapshots.borrow_mut();
+        snapshots.push(self.commitments.clone());
+        let height = Height {
+            revision_number: 0,
+            revision_height: snapshots.len() as u64,
+        };
+        let proof = digest(&self.commitments).to_vec();
+        (height, vec![proof; paths.len()])
+    }
+
+    fn client(&self) -> MemoryClient {
+        MemoryClient {
+            snapshots: self.snapshots.clone(),
+        }
+    }
+}
+
+impl MemoryClient {
+    /// Value hash at `path` in the snapshot at `height`.
+    fn proven_value(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<Option<H256>, VerifyError> {
+        let snapshots = self.snapshots.borrow();
+        let commitments = height
+            .revision_height
+            .checked_sub(1)
+            .and_then(|idx| snapshots.get(idx as usize))
+            .ok_or(VerifyError::Mpt)?;
+        if proof != digest(commitments) {
+            return Err(VerifyError::InvalidReceiptProof);
+        }
+        let CommitmentKV(path_hash, _) = CommitmentKV::deletion(path);
+        Ok(commitments.get(&path_hash).cloned())
+    }
+}
+
+impl Client for MemoryClient {
+    fn verify_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+        value: &[u8],
+    ) -> Result<(), VerifyError> {
+        let CommitmentKV(_, value_hash) = CommitmentKV::hash(path, value);
+        if self.proven_value(height, proof, path)? != Some(value_hash) {
+            return Err(VerifyError::Mpt);
+        }
+        Ok(())
+    }
+
+    fn verify_non_membership(
+        &self,
+        height: Height,
+        proof: &[u8],
+        path: &[u8],
+    ) -> Result<(), VerifyError> {
+        if self.proven_value(height, proof, path)?.is_some() {
+            return Err(VerifyError::Mpt);
+        }
+        Ok(())
+    }
+}
+
+/// IBC handler contract on a mock Axon chain. Proofs are real
+/// `AxonCommitmentProof`s and are verified by `AxonClient`.
+pub struct AxonBackend {
+    pub state: IbcHandlerState,
+    pub chain: MockAxonChain,
+}
+
+impl AxonBackend {
+    pub fn new(ibc_handler_address: [u8; 20]) -> Self {
+        Self {
+            state: IbcHandlerState::new(ibc_handler_address),
+            chain: MockAxonChain::new(4),
+        }
+    }
+}
+
+impl WriteOrVerifyCommitments for AxonBackend {
+    fn write_commitments<K, V>(
+        &mut self,
+        kvs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.state.write_commitments(kvs)
+    }
+
+    fn delete_commitments<K>(
+        &mut self,
+        paths: impl IntoIterator<Item = K>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.state.delete_commitments(paths)
+    }
+}
+
+impl Backend for AxonBackend {
+    type Client = AxonClient;
+
+    fn prove_paths(&mut self, paths: &[&str]) -> (Height, Vec<Vec<u8>>) {
+        self.state.axon_proofs(&mut self.chain, paths)
+    }
+
+    fn client(&self) -> AxonClient {
+        self.chain.client(self.state.ibc_handler_address).unwrap()
+    }
+}
+
+/// IBC cells of a chain, its commitment backend and a light client of the
+/// counterparty chain.
+pub struct Chain<B, C> {
+    pub connection_args: ConnectionArgs,
+    pub connections: IbcConnections,
+    /// Indexed by channel number.
+    pub channels: Vec<(ChannelArgs, IbcChannel)>,
+    pub packets: Vec<(PacketArgs, IbcPacket)>,
+    pub backend: B,
+    pub client: C,
+    /// Client of this chain on the counterparty chain, checked in the
+    /// connection handshake.
+    pub self_client: SelfClientStates,
+}
+
+impl<B, C> Chain<B, C> {
+    fn new(connection_args: ConnectionArgs, backend: B, client: C) -> Self {
+        Self {
+            connection_args,
+            connections: IbcConnections::default(),
+            channels: Vec::new(),
+            packets: Vec::new(),
+            backend,
+            client,
+            self_client: SelfClientStates {
+                client_state: connection_args.metadata_type_id.to_vec(),
+                consensus_height: Height::new(0, 1),
+                consensus_state: connection_args.ibc_handler_address.to_vec(),
+            },
+        }
+    }
+
+    pub fn client_id(&self) -> String {
+        self.connection_args.client_id()
+    }
+
+    pub fn connection_id(&self, idx: usize) -> String {
+        connection_id(&self.client_id(), idx)
+    }
+
+    pub fn connection(&self, idx: usize) -> Result<&ConnectionEnd, VerifyError> {
+        self.connections
+            .connections
+            .get(idx)
+            .ok_or(VerifyError::WrongConnectionId)
+    }
+
+    pub fn channel(&self, number: u64) -> Result<&(ChannelArgs, IbcChannel), VerifyError> {
+        self.channels
+            .get(number as usize)
+            .ok_or(VerifyError::WrongChannel)
+    }
+
+    /// Packet sent on `channel`.
+    pub fn sent_packet(&self, channel: u64, sequence: u64) -> Option<&IbcPacket> {
+        let idx = self.packet_idx(channel, sequence, false).ok()?;
+        Some(&self.packets[idx].1)
+    }
+
+    /// Packet received on `channel`.
+    pub fn received_packet(&self, channel: u64, sequence: u64) -> Option<&IbcPacket> {
+        let idx = self.packet_idx(channel, sequence, true).ok()?;
+        Some(&self.packets[idx].1)
+    }
+
+    fn packet_idx(
+        &self,
+        channel: u64,
+        sequence: u64,
+        received: bool,
+    ) -> Result<usize, VerifyError> {
+        self.packets
+            .iter()
+            .position(|(args, p)| {
+                args.channel_id == channel
+                    && args.sequence == sequence
+                    && matches!(p.status, PacketStatus::Recv | PacketStatus::WriteAck) == received
+            })
+            .ok_or(VerifyError::WrongPacketSequence)
+    }
+}
+
+impl<B: Backend, C> Chain<B, C> {
+    fn prove(&mut self, path: &str, fault: Option<Fault>) -> (Height, Vec<u8>) {
+        let (height, mut proofs) = self.prove_paths(&[path], fault);
+        (height, proofs.remove(0))
+    }
+
+    /// Prove `paths` at the same height. A `CorruptProof` fault breaks the
+    /// first proof.
+    fn prove_paths(&mut self, paths: &[&str], fault: Option<Fault>) -> (Height, Vec<Vec<u8>>) {
+        let (mut height, mut proofs) = self.backend.prove_paths(paths);
+        match (fault, proofs.first_mut()) {
+            (Some(Fault::CorruptProof), Some(proof)) => match proof.last_mut() {
+                Some(b) => *b ^= 1,
+                None => proof.push(0),
+            },
+            (Some(Fault::WrongProofHeight), _) => height.revision_height += 1,
+            _ => {}
+        }
+        (height, proofs)
+    }
+
+    /// Prove the connection `connection_id` and the client of
+    /// `counterparty` on this chain, for the connection handshake. Returns
+    /// the proofs of the connection, the client state and the consensus
+    /// state.
+    fn prove_handshake(
+        &mut self,
+        connection_id: &str,
+        counterparty: &SelfClientStates,
+        fault: Option<Fault>,
+    ) -> (Height, [Vec<u8>; 3]) {
+        let [client_path, consensus_path] = counterparty.paths(&self.connection_args.client_id());
+        let (height, proofs) = self.prove_paths(
+            &[
+                &connection_path(connection_id),
+                &client_path,
+                &consensus_path,
+            ],
+            fault,
+        );
+        let proofs = proofs.try_into().unwrap();
+        (height, proofs)
+    }
+}
+
+/// Ways to break the proof of the next step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    CorruptProof,
+    WrongProofHeight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    pub fn counterparty(self) -> Self {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+/// Two chains connected by IBC.
+pub struct Simulator<A: Backend, B: Backend> {
+    pub a: Chain<A, B::Client>,
+    pub b: Chain<B, A::Client>,
+    /// Fault injected into the proof of the next step.
+    pub fault: Option<Fault>,
+}
+
+/// Run `$body` with `$host` the chain at `$side` and `$cp` the counterparty.
+/// `$fault` takes the fault to inject into the proof of this step.
+macro_rules! on_side {
+    ($sim:ident, $side:expr, |$host:ident, $cp:ident, $fault:ident| $body:expr) => {{
+        let $fault = $sim.fault.take();
+        on_side!($sim, $side, |$host, $cp| $body)
+    }};
+    ($sim:ident, $side:expr, |$host:ident, $cp:ident| $body:expr) => {{
+        match $side {
+            Side::A => {
+                let ($host, $cp) = (&mut $sim.a, &mut $sim.b);
+                $body
+            }
+            Side::B => {
+                let ($host, $cp) = (&mut $sim.b, &mut $sim.a);
+                $body
+            }
+        }
+    }};
+}
+
+impl<A: Backend, B: Backend> Simulator<A, B> {
+    pub fn new(a_args: ConnectionArgs, a: A, b_args: ConnectionArgs, b: B) -> Self {
+        let client_of_a = a.client();
+        let client_of_b = b.client();
+        let mut sim = Self {
+            a: Chain::new(a_args, a, client_of_b),
+            b: Chain::new(b_args, b, client_of_a),
+            fault: None,
+        };
+        // Create the clients of each other.
+        let a_client = sim.b.self_client.commitments(&sim.a.client_id());
+        sim.a.backend.write_commitments(a_client).unwrap();
+        let b_client = sim.a.self_client.commitments(&sim.b.client_id());
+        sim.b.backend.write_commitments(b_client).unwrap();
+        sim
+    }
+
+    /// Open a connection from A to B. Returns indices of the connection on
+    /// A and B.
+    pub fn open_connection(&mut self) -> Result<(usize, usize), VerifyError> {
+        let a_idx = self.connection_open_init(Side::A)?;
+        let b_idx = self.connection_open_try(Side::B, a_idx)?;
+        self.connection_open_ack(Side::A, a_idx, b_idx)?;
+        self.connection_open_confirm(Side::B, b_idx)?;
+        Ok((a_idx, b_idx))
+    }
+
+    /// Open a channel from A to B on an open connection. Returns numbers of
+    /// the channel on A and B.
+    pub fn open_channel(
+        &mut self,
+        (a_conn, b_conn): (usize, usize),
+        (a_port, b_port): ([u8; 32], [u8; 32]),
+        order: Ordering,
+    ) -> Result<(u64, u64), VerifyError> {
+        let a_channel = self.channel_open_init(Side::A, a_conn, a_port, b_port, order)?;
+        let b_channel = self.channel_open_try(Side::B, b_conn, b_port, a_channel)?;
+        self.channel_open_ack(Side::A, a_channel, b_channel)?;
+        self.channel_open_confirm(Side::B, b_channel)?;
+        Ok((a_channel, b_channel))
+    }
+
+    /// Relay pending packets and acknowledgements in both directions until
+    /// there is nothing left. Returns the number of relayed messages.
+    pub fn relay_packets(&mut self) -> Result<usize, VerifyError> {
+        let mut relayed = 0;
+        loop {
+            let pending = self.pending(Side::A).or_else(|| self.pending(Side::B));
+            match pending {
+                Some((side, Pending::Recv(channel, sequence))) => {
+                    self.recv_packet(side, channel, sequence)?
+                }
+                Some((side, Pending::Ack(channel, sequence))) => {
+                    self.ack_packet(side, channel, sequence)?
+                }
+                None => return Ok(relayed),
+            }
+            relayed += 1;
+        }
+    }
+
+    /// Next message to relay to the chain at `side`.
+    fn pending(&self, side: Side) -> Option<(Side, Pending)> {
+        fn pending<H, HC, CP, CPC>(host: &Chain<H, HC>, cp: &Chain<CP, CPC>) -> Option<Pending> {
+            host.channels.iter().find_map(|(args, channel)| {
+                if channel.state != State::Open {
+                    return None;
+                }
+                let channel_id = args.channel_id_str();
+                // Sent by the counterparty and not received yet.
+                let recv = cp.packets.iter().find(|(_, p)| {
+                    p.status == PacketStatus::Send
+                        && p.packet.destination_channel_id == channel_id
+                        && p.packet.destination_port_id == channel.port_id
+                        && host
+                            .received_packet(args.channel_id, p.packet.sequence)
+                            .is_none()
+                });
+                if let Some((_, p)) = recv {
+                    return Some(Pending::Recv(args.channel_id, p.packet.sequence));
+                }
+                // Sent by this chain and acknowledged by the counterparty.
+                host.packets.iter().find_map(|(packet_args, p)| {
+                    let acked = p.status == PacketStatus::Send
+                        && packet_args.channel_id == args.channel_id
+                        && cp
+                            .received_packet(
+                                channel_number(&channel.counterparty.channel_id)?,
+                                p.packet.sequence,
+                            )
+                            .is_some_and(|p| p.status == PacketStatus::WriteAck);
+                    acked.then_some(Pending::Ack(args.channel_id, p.packet.sequence))
+                })
+            })
+        }
+
+        let pending = match side {
+            Side::A => pending(&self.a, &self.b),
+            Side::B => pending(&self.b, &self.a),
+        };
+        pending.map(|p| (side, p))
+    }
+
+    pub fn connection_open_init(&mut self, side: Side) -> Result<usize, VerifyError> {
+        on_side!(self, side, |host, cp| {
+            let mut new = host.connections.clone();
+            new.connections.push(ConnectionEnd {
+                state: State::Init,
+                counterparty: ConnectionCounterparty {
+                    client_id: cp.client_id(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            handle_msg_connection_open_init(
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                &mut host.backend,
+            )?;
+            host.connections = new;
+            Ok(host.connections.connections.len() - 1)
+        })
+    }
+
+    pub fn connection_open_try(
+        &mut self,
+        side: Side,
+        cp_conn: usize,
+    ) -> Result<usize, VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let cp_connection_id = cp.connection_id(cp_conn);
+            let (proof_height, [proof_init, proof_client, proof_consensus]) =
+                cp.prove_handshake(&cp_connection_id, &host.self_client, fault);
+            let mut new = host.connections.clone();
+            new.connections.push(ConnectionEnd {
+                state: State::OpenTry,
+                counterparty: ConnectionCounterparty {
+                    client_id: cp.client_id(),
+                    connection_id: cp_connection_id,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            handle_msg_connection_open_try(
+                &host.client,
+                &host.self_client,
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                &mut host.backend,
+                MsgConnectionOpenTry {
+                    proof_height,
+                    proof_init,
+                    client_state_of_b_on_a: host.self_client.client_state.clone(),
+                    proof_client_state_of_b_on_a: proof_client,
+                    consensus_height_of_b_on_a: host.self_client.consensus_height,
+                    consensus_state_of_b_on_a: host.self_client.consensus_state.clone(),
+                    proof_consensus_state_of_b_on_a: proof_consensus,
+                },
+            )?;
+            host.connections = new;
+            Ok(host.connections.connections.len() - 1)
+        })
+    }
+
+    pub fn connection_open_ack(
+        &mut self,
+        side: Side,
+        conn: usize,
+        cp_conn: usize,
+    ) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            host.connection(conn)?;
+            let cp_connection_id = cp.connection_id(cp_conn);
+            let (proof_height, [proof_try, proof_client, proof_consensus]) =
+                cp.prove_handshake(&cp_connection_id, &host.self_client, fault);
+            let mut new = host.connections.clone();
+            new.connections[conn].state = State::Open;
+            new.connections[conn].counterparty.connection_id = cp_connection_id;
+            handle_msg_connection_open_ack(
+                &host.client,
+                &host.self_client,
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                &mut host.backend,
+                MsgConnectionOpenAck {
+                    conn_id_on_a: conn,
+                    proof_height,
+                    proof_try,
+                    client_state_of_a_on_b: host.self_client.client_state.clone(),
+                    proof_client_state_of_a_on_b: proof_client,
+                    consensus_height_of_a_on_b: host.self_client.consensus_height,
+                    consensus_state_of_a_on_b: host.self_client.consensus_state.clone(),
+                    proof_consensus_state_of_a_on_b: proof_consensus,
+                },
+            )?;
+            host.connections = new;
+            Ok(())
+        })
+    }
+
+    pub fn connection_open_confirm(&mut self, side: Side, conn: usize) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let cp_connection_id = host.connection(conn)?.counterparty.connection_id.clone();
+            let (proof_height, proof_ack) = cp.prove(&connection_path(&cp_connection_id), fault);
+            let mut new = host.connections.clone();
+            new.connections[conn].state = State::Open;
+            handle_msg_connection_open_confirm(
+                &host.client,
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                &mut host.backend,
+                MsgConnectionOpenConfirm {
+                    conn_id_on_b: conn,
+                    proof_height,
+                    proof_ack,
+                },
+            )?;
+            host.connections = new;
+            Ok(())
+        })
+    }
+
+    pub fn channel_open_init(
+        &mut self,
+        side: Side,
+        conn: usize,
+        port_id: [u8; 32],
+        cp_port_id: [u8; 32],
+        order: Ordering,
+    ) -> Result<u64, VerifyError> {
+        on_side!(self, side, |host, _cp| {
+            let args = ChannelArgs {
+                metadata_type_id: host.connection_args.metadata_type_id,
+                ibc_handler_address: host.connection_args.ibc_handler_address,
+                open: false,
+                channel_id: host.connections.next_channel_number,
+                port_id,
+            };
+            let channel = IbcChannel {
+                number: args.channel_id,
+                port_id: args.port_id_str(),
+                state: State::Init,
+                order,
+                sequence: Sequence::default(),
+                counterparty: ChannelCounterparty {
+                    port_id: hex::encode(cp_port_id),
+                    channel_id: "".into(),
+                    connection_id: host.connection(conn)?.counterparty.connection_id.clone(),
+                },
+                connection_hops: vec![host.connection_id(conn)],
+                version: "ics20-1".into(),
+            };
+            let mut new = host.connections.clone();
+            new.next_channel_number += 1;
+            handle_msg_channel_open_init(
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                channel.clone(),
+                args,
+                &mut host.backend,
+            )?;
+            host.connections = new;
+            host.channels.push((args, channel));
+            Ok(args.channel_id)
+        })
+    }
+
+    pub fn channel_open_try(
+        &mut self,
+        side: Side,
+        conn: usize,
+        port_id: [u8; 32],
+        cp_channel: u64,
+    ) -> Result<u64, VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (cp_args, cp_channel) = cp.channel(cp_channel)?.clone();
+            let (proof_height, proof_init) = cp.prove(
+                &channel_path(&cp_channel.port_id, &cp_args.channel_id_str()),
+                fault,
+            );
+            let args = ChannelArgs {
+                metadata_type_id: host.connection_args.metadata_type_id,
+                ibc_handler_address: host.connection_args.ibc_handler_address,
+                open: false,
+                channel_id: host.connections.next_channel_number,
+                port_id,
+            };
+            let channel = IbcChannel {
+                number: args.channel_id,
+                port_id: args.port_id_str(),
+                state: State::OpenTry,
+                order: cp_channel.order,
+                sequence: Sequence::default(),
+                counterparty: ChannelCounterparty {
+                    port_id: cp_channel.port_id,
+                    channel_id: cp_args.channel_id_str(),
+                    connection_id: host.connection(conn)?.counterparty.connection_id.clone(),
+                },
+                connection_hops: vec![host.connection_id(conn)],
+                version: cp_channel.version,
+            };
+            let mut new = host.connections.clone();
+            new.next_channel_number += 1;
+            handle_msg_channel_open_try(
+                &host.client,
+                host.connections.clone(),
+                host.connection_args,
+                new.clone(),
+                host.connection_args,
+                channel.clone(),
+                args,
+                &mut host.backend,
+                MsgChannelOpenTry {
+                    proof_height,
+                    proof_init,
+                },
+            )?;
+            host.connections = new;
+            host.channels.push((args, channel));
+            Ok(args.channel_id)
+        })
+    }
+
+    pub fn channel_open_ack(
+        &mut self,
+        side: Side,
+        channel: u64,
+        cp_channel: u64,
+    ) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (old_args, old) = host.channel(channel)?.clone();
+            let (cp_args, cp_channel) = cp.channel(cp_channel)?.clone();
+            let (proof_height, proof_try) = cp.prove(
+                &channel_path(&cp_channel.port_id, &cp_args.channel_id_str()),
+                fault,
+            );
+            let mut new = old.clone();
+            new.state = State::Open;
+            new.version = cp_channel.version;
+            new.counterparty.channel_id = cp_args.channel_id_str();
+            let new_args = ChannelArgs {
+                open: true,
+                ..old_args
+            };
+            handle_msg_channel_open_ack(
+                &host.client,
+                old,
+                old_args,
+                new.clone(),
+                new_args,
+                &mut host.backend,
+                MsgChannelOpenAck {
+                    proof_height,
+                    proof_try,
+                },
+            )?;
+            host.channels[channel as usize] = (new_args, new);
+            Ok(())
+        })
+    }
+
+    pub fn channel_open_confirm(&mut self, side: Side, channel: u64) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (old_args, old) = host.channel(channel)?.clone();
+            let (proof_height, proof_ack) = cp.prove(
+                &channel_path(&old.counterparty.port_id, &old.counterparty.channel_id),
+                fault,
+            );
+            let mut new = old.clone();
+            new.state = State::Open;
+            let new_args = ChannelArgs {
+                open: true,
+                ..old_args
+            };
+            handle_msg_channel_open_confirm(
+                &host.client,
+                old,
+                old_args,
+                new.clone(),
+                new_args,
+                &mut host.backend,
+                MsgChannelOpenConfirm {
+                    proof_height,
+                    proof_ack,
+                },
+            )?;
+            host.channels[channel as usize] = (new_args, new);
+            Ok(())
+        })
+    }
+
+    pub fn channel_close_init(&mut self, side: Side, channel: u64) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, _cp| {
+            let (old_args, old) = host.channel(channel)?.clone();
+            let mut new = old.clone();
+            new.state = State::Closed;
+            let new_args = ChannelArgs {
+                open: false,
+                ..old_args
+            };
+            handle_msg_channel_close_init(old, old_args, new.clone(), new_args, &mut host.backend)?;
+            host.channels[channel as usize] = (new_args, new);
+            Ok(())
+        })
+    }
+
+    pub fn channel_close_confirm(&mut self, side: Side, channel: u64) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (old_args, old) = host.channel(channel)?.clone();
+            let (proof_height, proof_init) = cp.prove(
+                &channel_path(&old.counterparty.port_id, &old.counterparty.channel_id),
+                fault,
+            );
+            let mut new = old.clone();
+            new.state = State::Closed;
+            let new_args = ChannelArgs {
+                open: false,
+                ..old_args
+            };
+            handle_msg_channel_close_confirm(
+                &host.client,
+                old,
+                old_args,
+                new.clone(),
+                new_args,
+                &mut host.backend,
+                MsgChannelCloseConfirm {
+                    proof_height,
+                    proof_init,
+                },
+            )?;
+            host.channels[channel as usize] = (new_args, new);
+            Ok(())
+        })
+    }
+
+    /// Send a packet on `channel`. Returns its sequence.
+    pub fn send_packet(
+        &mut self,
+        side: Side,
+        channel: u64,
+        data: Vec<u8>,
+    ) -> Result<u64, VerifyError> {
+        on_side!(self, side, |host, _cp| {
+            let (args, old) = host.channel(channel)?.clone();
+            let sequence = old.sequence.next_sequence_sends;
+            let packet = IbcPacket {
+                packet: Packet {
+                    sequence,
+                    source_port_id: old.port_id.clone(),
+                    source_channel_id: args.channel_id_str(),
+                    destination_port_id: old.counterparty.port_id.clone(),
+                    destination_channel_id: old.counterparty.channel_id.clone(),
+                    data,
+                    timeout_height: Default::default(),
+                    timeout_timestamp: 0,
+                },
+                status: PacketStatus::Send,
+                ack: None,
+            };
+            let packet_args = PacketArgs {
+                ibc_handler_address: args.ibc_handler_address,
+                channel_id: args.channel_id,
+                port_id: args.port_id,
+                sequence,
+            };
+            let mut new = old.clone();
+            new.sequence.next_sequence_sends += 1;
+            handle_msg_send_packet(
+                old,
+                args,
+                new.clone(),
+                args,
+                packet.clone(),
+                packet_args,
+                &mut host.backend,
+            )?;
+            host.channels[channel as usize].1 = new;
+            host.packets.push((packet_args, packet));
+            Ok(sequence)
+        })
+    }
+
+    /// Receive packet `sequence` sent by the counterparty to `channel`.
+    pub fn recv_packet(
+        &mut self,
+        side: Side,
+        channel: u64,
+        sequence: u64,
+    ) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (args, old) = host.channel(channel)?.clone();
+            let cp_channel =
+                channel_number(&old.counterparty.channel_id).ok_or(VerifyError::WrongChannel)?;
+            let sent = cp
+                .sent_packet(cp_channel, sequence)
+                .ok_or(VerifyError::WrongPacketSequence)?
+                .clone();
+            let (proof_height, proof_commitment) = cp.prove(
+                &packet_commitment_path(
+                    &sent.packet.source_port_id,
+                    &sent.packet.source_channel_id,
+                    sequence,
+                ),
+                fault,
+            );
+            let mut new = old.clone();
+            if new.order == Ordering::Unordered {
+                new.sequence.unorder_receive(sequence)?;
+            } else {
+                new.sequence.next_sequence_recvs += 1;
+            }
+            let packet = IbcPacket {
+                packet: sent.packet,
+                status: PacketStatus::Recv,
+                ack: None,
+            };
+            let packet_args = PacketArgs {
+                ibc_handler_address: args.ibc_handler_address,
+                channel_id: args.channel_id,
+                port_id: args.port_id,
+                sequence,
+            };
+            handle_msg_recv_packet(
+                &host.client,
+                old,
+                args,
+                new.clone(),
+                args,
+                None,
+                packet.clone(),
+                packet_args,
+                &mut host.backend,
+                MsgRecvPacket {
+                    proof_height,
+                    proof_commitment,
+                },
+            )?;
+            host.channels[channel as usize].1 = new;
+            host.packets.push((packet_args, packet));
+            Ok(())
+        })
+    }
+
+    /// Write acknowledgement of received packet `sequence` on `channel`.
+    pub fn write_ack(
+        &mut self,
+        side: Side,
+        channel: u64,
+        sequence: u64,
+        ack: Vec<u8>,
+    ) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, _cp| {
+            let (args, channel) = host.channel(channel)?.clone();
+            let idx = host.packet_idx(args.channel_id, sequence, true)?;
+            let (packet_args, old) = host.packets[idx].clone();
+            let mut new = old.clone();
+            new.status = PacketStatus::WriteAck;
+            new.ack = Some(ack);
+            handle_msg_write_ack_packet(
+                channel.clone(),
+                args,
+                channel,
+                args,
+                old,
+                packet_args,
+                new.clone(),
+                packet_args,
+                &mut host.backend,
+            )?;
+            host.packets[idx].1 = new;
+            Ok(())
+        })
+    }
+
+    /// Acknowledge packet `sequence` sent on `channel` with the
+    /// acknowledgement written by the counterparty.
+    pub fn ack_packet(
+        &mut self,
+        side: Side,
+        channel: u64,
+        sequence: u64,
+    ) -> Result<(), VerifyError> {
+        on_side!(self, side, |host, cp, fault| {
+            let (args, old_channel) = host.channel(channel)?.clone();
+            let idx = host.packet_idx(args.channel_id, sequence, false)?;
+            let (packet_args, old) = host.packets[idx].clone();
+            let cp_channel = channel_number(&old_channel.counterparty.channel_id)
+                .ok_or(VerifyError::WrongChannel)?;
+            let ack = cp
+                .received_packet(cp_channel, sequence)
+                .and_then(|p| p.ack.clone())
+                .ok_or(VerifyError::WrongPacketAck)?;
+            let (proof_height, proof_acked) = cp.prove(
+                &packet_acknowledgement_commitment_path(
+                    &old.packet.destination_port_id,
+                    &old.packet.destination_channel_id,
+                    sequence,
+                ),
+                fault,
+            );
+            let mut new_channel = old_channel.clone();
+            if new_channel.order != Ordering::Unordered {
+                new_channel.sequence.next_sequence_acks += 1;
+            }
+            let mut new = old.clone();
+            new.status = PacketStatus::Ack;
+            new.ack = Some(ack);
+            handle_msg_ack_packet(
+                &host.client,
+                old_channel,
+                args,
+                new_channel.clone(),
+                args,
+                old,
+                packet_args,
+                new.clone(),
+                packet_args,
+                &mut host.backend,
+                MsgAckPacket {
+                    proof_height,
+                    proof_acked,
+                },
+            )?;
+            host.channels[channel as usize].1 = new_channel;
+            host.packets[idx].1 = new;
+            Ok(())
+        })
+    }
+}
+
+enum Pending {
+    Recv(u64, u64),
+    Ack(u64, u64),
+}
+
+fn channel_number(channel_id: &str) -> Option<u64> {
+    channel_id
+        .strip_prefix(crate::consts::CHANNEL_ID_PREFIX)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod model;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(seed: u8) -> ConnectionArgs {
+        ConnectionArgs {
+            metadata_type_id: [seed; 32],
+            ibc_handler_address: [seed; 20],
+        }
+    }
+
+    fn memory_simulator() -> Simulator<MemoryBackend, MemoryBackend> {
+        Simulator::new(
+            args(1),
+            MemoryBackend::default(),
+            args(2),
+            MemoryBackend::default(),
+        )
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        let mut sim = memory_simulator();
+        let conn = sim.open_connection().unwrap();
+        let (a, b) = sim
+            .open_channel(conn, ([7; 32], [9; 32]), Ordering::Unordered)
+            .unwrap();
+
+        let seq = sim.send_packet(Side::A, a, b"ping".to_vec()).unwrap();
+        let packet = &sim.a.sent_packet(a, seq).unwrap().packet;
+        let CommitmentKV(path_hash, _) = CommitmentKV::deletion(packet_commitment_path(
+            &packet.source_port_id,
+            &packet.source_channel_id,
+            seq,
+        ));
+        assert!(sim.a.backend.commitments.contains_key(&path_hash));
+        assert_eq!(sim.relay_packets().unwrap(), 1);
+        sim.write_ack(Side::B, b, seq, b"pong".to_vec()).unwrap();
+        assert_eq!(sim.relay_packets().unwrap(), 1);
+        let packet = sim.a.sent_packet(a, seq).unwrap();
+        assert_eq!(packet.status, PacketStatus::Ack);
+        assert_eq!(packet.ack.as_deref(), Some(&b"pong"[..]));
+        // The packet commitment is deleted on ack.
+        assert!(!sim.a.backend.commitments.contains_key(&path_hash));
+
+        sim.channel_close_init(Side::B, b).unwrap();
+        sim.channel_close_confirm(Side::A, a).unwrap();
+        assert_eq!(sim.a.channel(a).unwrap().1.state, State::Closed);
+    }
+
+    #[test]
+    fn test_ordered_channel_both_directions() {
+        let mut sim = memory_simulator();
+        let conn = sim.open_connection().unwrap();
+        let (a, b) = sim
+            .open_channel(conn, ([7; 32], [9; 32]), Ordering::Ordered)
+            .unwrap();
+
+        for _ in 0..2 {
+            sim.send_packet(Side::A, a, b"a".to_vec()).unwrap();
+            sim.send_packet(Side::B, b, b"b".to_vec()).unwrap();
+        }
+        assert_eq!(sim.relay_packets().unwrap(), 4);
+        assert_eq!(sim.b.channel(b).unwrap().1.sequence.next_sequence_recvs, 3);
+        assert_eq!(sim.a.channel(a).unwrap().1.sequence.next_sequence_recvs, 3);
+    }
+
+    #[test]
+    fn test_faults() {
+        for fault in [Fault::CorruptProof, Fault::WrongProofHeight] {
+            let mut sim = memory_simulator();
+            let a_conn = sim.connection_open_init(Side::A).unwrap();
+            sim.fault = Some(fault);
+            sim.connection_open_try(Side::B, a_conn).unwrap_err();
+            // Cells are unchanged after a failed step, so the step can be
+            // retried.
+            assert!(sim.b.connections.connections.is_empty());
+            sim.connection_open_try(Side::B, a_conn).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_replayed_packet() {
+        let mut sim = memory_simulator();
+        let conn = sim.open_connection().unwrap();
+        let (a, b) = sim
+            .open_channel(conn, ([7; 32], [9; 32]), Ordering::Unordered)
+            .unwrap();
+        let seq = sim.send_packet(Side::A, a, b"ping".to_vec()).unwrap();
+        sim.recv_packet(Side::B, b, seq).unwrap();
+        assert!(matches!(
+            sim.recv_packet(Side::B, b, seq),
+            Err(VerifyError::WrongPacketSequence)
+        ));
+    }
+
+    #[test]
+    fn test_axon_chains() {
+        let mut sim = Simulator::new(
+            args(1),
+            AxonBackend::new(args(1).ibc_handler_address),
+            args(2),
+            AxonBackend::new(args(2).ibc_handler_address),
+        );
+        let conn = sim.open_connection().unwrap();
+        let (a, b) = sim
+            .open_channel(conn, ([7; 32], [9; 32]), Ordering::Unordered)
+            .unwrap();
+        let seq = sim.send_packet(Side::B, b, b"ping".to_vec()).unwrap();
+        sim.relay_packets().unwrap();
+        sim.write_ack(Side::A, a, seq, b"pong".to_vec()).unwrap();
+        sim.fault = Some(Fault::CorruptProof);
+        sim.relay_packets().unwrap_err();
+        sim.relay_packets().unwrap();
+        assert_eq!(sim.b.sent_packet(b, seq).unwrap().status, PacketStatus::Ack);
+    }
+}
@@ -0,0 +1,691 @@
+//! Reference model of ICS-03 connection and ICS-04 channel and packet state
+//! machines, checked against the handlers with random sequences of steps.
+
+use std::collections::BTreeMap;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use super::*;
+
+#[derive(Debug, Clone)]
+enum Action {
+    ConnOpenInit(Side),
+    ConnOpenTry(Side, usize),
+    ConnOpenAck(Side, usize, usize),
+    ConnOpenConfirm(Side, usize),
+    ChanOpenInit(Side, usize, Ordering),
+    ChanOpenTry(Side, usize, u64),
+    ChanOpenAck(Side, u64, u64),
+    ChanOpenConfirm(Side, u64),
+    ChanCloseInit(Side, u64),
+    ChanCloseConfirm(Side, u64),
+    SendPacket(Side, u64),
+    RecvPacket(Side, u64, u64),
+    WriteAck(Side, u64, u64),
+    AckPacket(Side, u64, u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ModelConnection {
+    state: State,
+    counterparty: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ModelChannel {
+    state: State,
+    connection: usize,
+    order: Ordering,
+    counterparty: Option<u64>,
+    next_send: u64,
+    next_recv: u64,
+    next_ack: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ModelSentPacket {
+    destination: Option<u64>,
+    acked: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ModelChain {
+    connections: Vec<ModelConnection>,
+    channels: Vec<ModelChannel>,
+    /// (channel, sequence) -> sent packet.
+    sent: BTreeMap<(u64, u64), ModelSentPacket>,
+    /// (channel, sequence) -> whether acknowledgement is written.
+    received: BTreeMap<(u64, u64), bool>,
+}
+
+#[derive(Debug, Default)]
+struct Model {
+    a: ModelChain,
+    b: ModelChain,
+}
+
+impl Model {
+    /// Apply `action` if it is valid. Returns whether it is valid.
+    fn apply(&mut self, action: &Action) -> bool {
+        use Action::*;
+
+        let side = match *action {
+            ConnOpenInit(side)
+            | ConnOpenTry(side, ..)
+            | ConnOpenAck(side, ..)
+            | ConnOpenConfirm(side, ..)
+            | ChanOpenInit(side, ..)
+            | ChanOpenTry(side, ..)
+            | ChanOpenAck(side, ..)
+            | ChanOpenConfirm(side, ..)
+            | ChanCloseInit(side, ..)
+            | ChanCloseConfirm(side, ..)
+            | SendPacket(side, ..)
+            | RecvPacket(side, ..)
+            | WriteAck(side, ..)
+            | AckPacket(side, ..) => side,
+        };
+        let (host, cp) = match side {
+            Side::A => (&mut self.a, &self.b),
+            Side::B => (&mut self.b, &self.a),
+        };
+
+        // Whether the connection of host channel `ch` is connected to the
+        // connection of counterparty channel `cp_ch`.
+        let connected = |host: &ModelChain, ch: &ModelChannel, cp_ch: &ModelChannel| {
+            host.connections[ch.connection].counterparty == Some(cp_ch.connection)
+        };
+
+        match *action {
+            ConnOpenInit(_) => {
+                host.connections.push(ModelConnection {
+                    state: State::Init,
+                    counterparty: None,
+                });
+                true
+            }
+            ConnOpenTry(_, cp_conn) => {
+                let valid = cp
+                    .connections
+                    .get(cp_conn)
+                    .is_some_and(|c| c.state == State::Init);
+                if valid {
+                    host.connections.push(ModelConnection {
+                        state: State::OpenTry,
+                        counterparty: Some(cp_conn),
+                    });
+                }
+                valid
+            }
+            ConnOpenAck(_, conn, cp_conn) => {
+                let valid = host
+                    .connections
+                    .get(conn)
+                    .is_some_and(|c| c.state == State::Init)
+                    && cp.connections.get(cp_conn)
+                        == Some(&ModelConnection {
+                            state: State::OpenTry,
+                            counterparty: Some(conn),
+                        });
+                if valid {
+                    host.connections[conn] = ModelConnection {
+                        state: State::Open,
+                        counterparty: Some(cp_conn),
+                    };
+                }
+                valid
+            }
+            ConnOpenConfirm(_, conn) => {
+                let valid = match host.connections.get(conn) {
+                    Some(&ModelConnection {
+                        state: State::OpenTry,
+                        counterparty: Some(cp_conn),
+                    }) => {
+                        cp.connections.get(cp_conn)
+                            == Some(&ModelConnection {
+                                state: State::Open,
+                                counterparty: Some(conn),
+                            })
+                    }
+                    _ => false,
+                };
+                if valid {
+                    host.connections[conn].state = State::Open;
+                }
+                valid
+            }
+            ChanOpenInit(_, conn, order) => {
+                let valid = host
+                    .connections
+                    .get(conn)
+                    .is_some_and(|c| c.state == State::Open);
+                if valid {
+                    host.channels
+                        .push(ModelChannel::new(State::Init, conn, order, None));
+                }
+                valid
+            }
+            ChanOpenTry(_, conn, cp_ch) => {
+                let valid = match (host.connections.get(conn), cp.channel(cp_ch)) {
+                    (Some(c), Some(cp_channel)) => {
+                        c.state == State::Open
+                            && cp_channel.state == State::Init
+                            && c.counterparty == Some(cp_channel.connection)
+                    }
+                    _ => false,
+                };
+                if valid {
+                    let order = cp.channel(cp_ch).unwrap().order;
+                    host.channels
+                        .push(ModelChannel::new(State::OpenTry, conn, order, Some(cp_ch)));
+                }
+                valid
+            }
+            ChanOpenAck(_, ch, cp_ch) => {
+                let valid = match (host.channel(ch), cp.channel(cp_ch)) {
+                    (Some(channel), Some(cp_channel)) => {
+                        channel.state == State::Init
+                            && cp_channel.state == State::OpenTry
+                            && cp_channel.counterparty == Some(ch)
+                            && cp_channel.order == channel.order
+                            && connected(host, channel, cp_channel)
+                    }
+                    _ => false,
+                };
+                if valid {
+                    let channel = &mut host.channels[ch as usize];
+                    channel.state = State::Open;
+                    channel.counterparty = Some(cp_ch);
+                }
+                valid
+            }
+            ChanOpenConfirm(_, ch) | ChanCloseConfirm(_, ch) => {
+                let (state, cp_state) = match action {
+                    ChanOpenConfirm(..) => (State::OpenTry, State::Open),
+                    _ => (State::Open, State::Closed),
+                };
+                let valid = match host.channel(ch) {
+                    Some(channel) if channel.state == state => channel
+                        .counterparty
+                        .and_then(|cp_ch| cp.channel(cp_ch))
+                        .is_some_and(|cp_channel| {
+                            cp_channel.state == cp_state
+                                && cp_channel.counterparty == Some(ch)
+                                && cp_channel.order == channel.order
+                                && connected(host, channel, cp_channel)
+                        }),
+                    _ => false,
+                };
+                if valid {
+                    host.channels[ch as usize].state = match action {
+                        ChanOpenConfirm(..) => State::Open,
+                        _ => State::Closed,
+                    };
+                }
+                valid
+            }
+            ChanCloseInit(_, ch) => {
+                let valid = host.channel(ch).is_some_and(|c| c.state == State::Open);
+                if valid {
+                    host.channels[ch as usize].state = State::Closed;
+                }
+                valid
+            }
+            SendPacket(_, ch) => {
+                let valid = host.channel(ch).is_some_and(|c| c.state == State::Open);
+                if valid {
+                    let channel = &mut host.channels[ch as usize];
+                    host.sent.insert(
+                        (ch, channel.next_send),
+                        ModelSentPacket {
+                            destination: channel.counterparty,
+                            acked: false,
+                        },
+                    );
+                    channel.next_send += 1;
+                }
+                valid
+            }
+            RecvPacket(_, ch, seq) => {
+                let valid = match host.channel(ch) {
+                    Some(channel) if channel.state == State::Open => channel
+                        .counterparty
+                        .and_then(|cp_ch| cp.sent.get(&(cp_ch, seq)))
+                        .is_some_and(|packet| {
+                            packet.destination == Some(ch)
+                                && match channel.order {
+                                    Ordering::Ordered => seq == channel.next_recv,
+                                    _ => !host.received.contains_key(&(ch, seq)),
+                                }
+                        }),
+                    _ => false,
+                };
+                if valid {
+                    host.received.insert((ch, seq), false);
+                    let channel = &mut host.channels[ch as usize];
+                    if channel.order == Ordering::Ordered {
+                        channel.next_recv += 1;
+                    }
+                }
+                valid
+            }
+            WriteAck(_, ch, seq) => {
+                let valid = host.channel(ch).is_some_and(|c| c.state == State::Open)
+                    && host.received.get(&(ch, seq)) == Some(&false);
+                if valid {
+                    host.received.insert((ch, seq), true);
+                }
+                valid
+            }
+            AckPacket(_, ch, seq) => {
+                // ICS-04 acknowledgePacket requires an open channel.
+                let valid = match (host.channel(ch), host.sent.get(&(ch, seq))) {
+                    (Some(channel), Some(packet)) => {
+                        channel.state == State::Open
+                            && !packet.acked
+                            && channel.counterparty.is_some()
+                            && packet.destination == channel.counterparty
+                            && cp.received.get(&(channel.counterparty.unwrap(), seq)) == Some(&true)
+                            && (channel.order != Ordering::Ordered || seq == channel.next_ack)
+                    }
+                    _ => false,
+                };
+                if valid {
+                    host.sent.get_mut(&(ch, seq)).unwrap().acked = true;
+                    let channel = &mut host.channels[ch as usize];
+                    if channel.order == Ordering::Ordered {
+                        channel.next_ack += 1;
+                    }
+                }
+                valid
+            }
+        }
+    }
+}
+
+impl ModelChannel {
+    fn new(state: State, connection: usize, order: Ordering, counterparty: Option<u64>) -> Self {
+        Self {
+            state,
+            connection,
+            order,
+            counterparty,
+            next_send: 1,
+            next_recv: 1,
+            next_ack: 1,
+        }
+    }
+}
+
+impl ModelChain {
+    fn channel(&self, ch: u64) -> Option<&ModelChannel> {
+        self.channels.get(ch as usize)
+    }
+}
+
+fn connection_index(connection_id: &str) -> Option<usize> {
+    connection_id.rsplit('-').next()?.parse().ok()
+}
+
+/// Abstract state of a simulated chain, to compare with the model.
+fn observe<B, C>(chain: &Chain<B, C>) -> ModelChain {
+    let connections = chain
+        .connections
+        .connections
+        .iter()
+        .map(|c| ModelConnection {
+            state: c.state,
+            counterparty: connection_index(&c.counterparty.connection_id),
+        })
+        .collect();
+    let channels = chain
+        .channels
+        .iter()
+        .map(|(_, c)| ModelChannel {
+            state: c.state,
+            connection: connection_index(&c.connection_hops[0]).unwrap(),
+            order: c.order,
+            counterparty: channel_number(&c.counterparty.channel_id),
+            next_send: c.sequence.next_sequence_sends,
+            next_recv: c.sequence.next_sequence_recvs,
+            next_ack: c.sequence.next_sequence_acks,
+        })
+        .collect();
+    let mut sent = BTreeMap::new();
+    let mut received = BTreeMap::new();
+    for (args, p) in &chain.packets {
+        let key = (args.channel_id, args.sequence);
+        match p.status {
+            PacketStatus::Send | PacketStatus::Ack => {
+                sent.insert(
+                    key,
+                    ModelSentPacket {
+                        destination: channel_number(&p.packet.destination_channel_id),
+                        acked: p.status == PacketStatus::Ack,
+                    },
+                );
+            }
+            PacketStatus::Recv | PacketStatus::WriteAck => {
+                received.insert(key, p.status == PacketStatus::WriteAck);
+            }
+        }
+    }
+    ModelChain {
+        connections,
+        channels,
+        sent,
+        received,
+    }
+}
+
+fn port(side: Side) -> [u8; 32] {
+    match side {
+        Side::A => [7; 32],
+        Side::B => [9; 32],
+    }
+}
+
+fn run(
+    sim: &mut Simulator<MemoryBackend, MemoryBackend>,
+    action: &Action,
+) -> Result<(), VerifyError> {
+    use Action::*;
+
+    match *action {
+        ConnOpenInit(side) => sim.connection_open_init(side).map(drop),
+        ConnOpenTry(side, cp_conn) => sim.connection_open_try(side, cp_conn).map(drop),
+        ConnOpenAck(side, conn, cp_conn) => sim.connection_open_ack(side, conn, cp_conn),
+        ConnOpenConfirm(side, conn) => sim.connection_open_confirm(side, conn),
+        ChanOpenInit(side, conn, order) => sim
+            .channel_open_init(side, conn, port(side), port(side.counterparty()), order)
+            .map(drop),
+        ChanOpenTry(side, conn, cp_ch) => sim
+            .channel_open_try(side, conn, port(side), cp_ch)
+            .map(drop),
+        ChanOpenAck(side, ch, cp_ch) => sim.channel_open_ack(side, ch, cp_ch),
+        ChanOpenConfirm(side, ch) => sim.channel_open_confirm(side, ch),
+        ChanCloseInit(side, ch) => sim.channel_close_init(side, ch),
+        ChanCloseConfirm(side, ch) => sim.channel_close_confirm(side, ch),
+        SendPacket(side, ch) => sim.send_packet(side, ch, b"data".to_vec()).map(drop),
+        RecvPacket(side, ch, seq) => sim.recv_packet(side, ch, seq),
+        WriteAck(side, ch, seq) => sim.write_ack(side, ch, seq, b"ack".to_vec()),
+        AckPacket(side, ch, seq) => sim.ack_packet(side, ch, seq),
+    }
+}
+
+/// Open a connection and a channel between A and B.
+fn handshake(order: Ordering) -> Vec<Action> {
+    use Action::*;
+
+    vec![
+        ConnOpenInit(Side::A),
+        ConnOpenTry(Side::B, 0),
+        ConnOpenAck(Side::A, 0, 0),
+        ConnOpenConfirm(Side::B, 0),
+        ChanOpenInit(Side::A, 0, order),
+        ChanOpenTry(Side::B, 0, 0),
+        ChanOpenAck(Side::A, 0, 0),
+        ChanOpenConfirm(Side::B, 0),
+    ]
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    use Action::*;
+
+    let side = prop_oneof![Just(Side::A), Just(Side::B)];
+    let order = prop_oneof![Just(Ordering::Unordered), Just(Ordering::Ordered)];
+    let idx = 0..3usize;
+    let ch = 0..3u64;
+    let seq = 1..4u64;
+    prop_oneof![
+        1 => side.clone().prop_map(ConnOpenInit),
+        1 => (side.clone(), idx.clone()).prop_map(|(s, c)| ConnOpenTry(s, c)),
+        1 => (side.clone(), idx.clone(), idx.clone()).prop_map(|(s, c, cp)| ConnOpenAck(s, c, cp)),
+        1 => (side.clone(), idx.clone()).prop_map(|(s, c)| ConnOpenConfirm(s, c)),
+        1 => (side.clone(), idx.clone(), order).prop_map(|(s, c, o)| ChanOpenInit(s, c, o)),
+        1 => (side.clone(), idx, ch.clone()).prop_map(|(s, c, cp)| ChanOpenTry(s, c, cp)),
+        1 => (side.clone(), ch.clone(), ch.clone()).prop_map(|(s, c, cp)| ChanOpenAck(s, c, cp)),
+        1 => (side.clone(), ch.clone()).prop_map(|(s, c)| ChanOpenConfirm(s, c)),
+        1 => (side.clone(), ch.clone()).prop_map(|(s, c)| ChanCloseInit(s, c)),
+        1 => (side.clone(), ch.clone()).prop_map(|(s, c)| ChanCloseConfirm(s, c)),
+        3 => (side.clone(), ch.clone()).prop_map(|(s, c)| SendPacket(s, c)),
+        3 => (side.clone(), ch.clone(), seq.clone()).prop_map(|(s, c, q)| RecvPacket(s, c, q)),
+        3 => (side.clone(), ch.clone(), seq.clone()).prop_map(|(s, c, q)| WriteAck(s, c, q)),
+        3 => (side, ch, seq).prop_map(|(s, c, q)| AckPacket(s, c, q)),
+    ]
+}
+
+/// Run `actions` on the simulator and the model, after a handshake if
+/// `handshake_order` is given. Returns whether each action succeeded.
+fn check(
+    handshake_order: Option<Ordering>,
+    actions: Vec<Action>,
+) -> Result<Vec<bool>, TestCaseError> {
+    let mut sim = Simulator::new(
+        ConnectionArgs {
+            metadata_type_id: [1; 32],
+            ibc_handler_address: [1; 20],
+        },
+        MemoryBackend::default(),
+        ConnectionArgs {
+            metadata_type_id: [2; 32],
+            ibc_handler_address: [2; 20],
+        },
+        MemoryBackend::default(),
+    );
+    let mut model = Model::default();
+
+    let prefix = handshake_order.map(handshake).unwrap_or_default();
+    let mut results = Vec::new();
+    for action in prefix.iter().chain(&actions) {
+        let expected = model.apply(action);
+        let result = run(&mut sim, action);
+        prop_assert_eq!(result.is_ok(), expected, "{:?}: {:?}", action, result);
+        prop_assert_eq!(&observe(&sim.a), &model.a, "{:?}", action);
+        prop_assert_eq!(&observe(&sim.b), &model.b, "{:?}", action);
+        results.push(expected);
+    }
+    Ok(results)
+}
+
+proptest!(
+    #[test]
+    fn prop_test_handlers_follow_model(
+        handshake_order in proptest::option::of(prop_oneof![
+            Just(Ordering::Unordered),
+            Just(Ordering::Ordered),
+        ]),
+        actions in vec(action(), 1..64),
+    ) {
+        check(handshake_order, actions)?;
+    }
+);
+
+#[test]
+fn test_ack_never_sent_packet() {
+    use Action::*;
+
+    let mut actions = handshake(Ordering::Unordered);
+    actions.extend([
+        RecvPacket(Side::B, 0, 1),
+        AckPacket(Side::A, 0, 1),
+        SendPacket(Side::A, 0),
+        // Not received yet.
+        AckPacket(Side::A, 0, 1),
+    ]);
+    let results = check(None, actions).unwrap();
+    assert_eq!(results[8..], [false, false, true, false]);
+}
+
+#[test]
+fn test_recv_on_closed_channel() {
+    use Action::*;
+
+    let mut actions = handshake(Ordering::Ordered);
+    actions.extend([
+        SendPacket(Side::A, 0),
+        ChanCloseInit(Side::B, 0),
+        RecvPacket(Side::B, 0, 1),
+        ChanCloseConfirm(Side::A, 0),
+        SendPacket(Side::A, 0),
+    ]);
+    let results = check(None, actions).unwrap();
+    assert_eq!(results[8..], [true, true, false, true, false]);
+}
+
+#[test]
+fn test_ack_on_closed_channel() {
+    use Action::*;
+
+    let mut actions = handshake(Ordering::Unordered);
+    actions.extend([
+        SendPacket(Side::A, 0),
+        RecvPacket(Side::B, 0, 1),
+        WriteAck(Side::B, 0, 1),
+        ChanCloseInit(Side::A, 0),
+        AckPacket(Side::A, 0, 1),
+    ]);
+    let results = check(None, actions).unwrap();
+    assert_eq!(results[8..], [true, true, true, true, false]);
+}
+
+/// Accepts all proofs, to check handlers on arbitrary cells.
+struct AcceptAll;
+
+impl Client for AcceptAll {
+    fn verify_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        _path: &[u8],
+        _value: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+
+    fn verify_non_membership(
+        &self,
+        _height: Height,
+        _proof: &[u8],
+        _path: &[u8],
+    ) -> Result<(), VerifyError> {
+        Ok(())
+    }
+}
+
+fn channel_state() -> impl Strategy<Value = State> {
+    prop_oneof![
+        Just(State::Init),
+        Just(State::OpenTry),
+        Just(State::Open),
+        Just(State::Closed),
+    ]
+}
+
+fn packet_status() -> impl Strategy<Value = PacketStatus> {
+    prop_oneof![
+        Just(PacketStatus::Send),
+        Just(PacketStatus::Recv),
+        Just(PacketStatus::WriteAck),
+        Just(PacketStatus::Ack),
+    ]
+}
+
+/// Old and new cells of a packet on an unordered channel, with arbitrary
+/// statuses and acks.
+fn packet_cells(
+    channel_state: State,
+    old_status: PacketStatus,
+    new_status: PacketStatus,
+    old_ack: Option<Vec<u8>>,
+    new_ack: Option<Vec<u8>>,
+) -> (IbcChannel, IbcPacket, IbcPacket) {
+    let channel = IbcChannel {
+        state: channel_state,
+        order: Ordering::Unordered,
+        ..Default::default()
+    };
+    let packet = Packet {
+        sequence: 1,
+        ..Default::default()
+    };
+    let old = IbcPacket {
+        packet: packet.clone(),
+        status: old_status,
+        ack: old_ack,
+    };
+    let new = IbcPacket {
+        packet,
+        status: new_status,
+        ack: new_ack,
+    };
+    (channel, old, new)
+}
+
+proptest!(
+    /// ICS-04 writeAcknowledgement: the channel is open and the packet goes
+    /// from received to acknowledgement written.
+    #[test]
+    fn prop_test_write_ack_cells(
+        state in channel_state(),
+        old_status in packet_status(),
+        new_status in packet_status(),
+        old_ack in proptest::option::of(vec(any::<u8>(), 0..4)),
+        new_ack in proptest::option::of(vec(any::<u8>(), 0..4)),
+    ) {
+        let valid = state == State::Open
+            && old_status == PacketStatus::Recv
+            && new_status == PacketStatus::WriteAck
+            && old_ack.is_none()
+            && new_ack.is_some();
+        let (channel, old, new) = packet_cells(state, old_status, new_status, old_ack, new_ack);
+        let result = handle_msg_write_ack_packet(
+            channel.clone(),
+            ChannelArgs::default(),
+            channel,
+            ChannelArgs::default(),
+            old,
+            PacketArgs::default(),
+            new,
+            PacketArgs::default(),
+            &mut Vec::new(),
+        );
+        prop_assert_eq!(result.is_ok(), valid, "{:?}", result);
+    }
+
+    /// ICS-04 acknowledgePacket: the channel is open and the sent packet
+    /// gets the acknowledgement.
+    #[test]
+    fn prop_test_ack_packet_cells(
+        state in channel_state(),
+        old_status in packet_status(),
+        new_status in packet_status(),
+        old_ack in proptest::option::of(vec(any::<u8>(), 0..4)),
+        new_ack in proptest::option::of(vec(any::<u8>(), 0..4)),
+    ) {
+        let valid = state == State::Open
+            && old_status == PacketStatus::Send
+            && new_status == PacketStatus::Ack
+            && old_ack.is_none()
+            && new_ack.is_some();
+        let (channel, old, new) = packet_cells(state, old_status, new_status, old_ack, new_ack);
+        let result = handle_msg_ack_packet(
+            AcceptAll,
+            channel.clone(),
+            ChannelArgs::default(),
+            channel,
+            ChannelArgs::default(),
+            old,
+            PacketArgs::default(),
+            new,
+            PacketArgs::default(),
+            &mut Vec::new(),
+            MsgAckPacket {
+                proof_height: Height::default(),
+                proof_acked: vec![],
+            },
+        );
+        prop_assert_eq!(result.is_ok(), valid, "{:?}", result);
+    }
+);